@@ -0,0 +1,29 @@
+#version 450 core
+
+#define GRR 1
+
+#if GRR
+layout (location = 0) uniform float u_opacity;
+layout (location = 1) uniform float u_over_white;
+layout (binding = 1) uniform sampler2D u_layer;
+#else
+layout (binding = 0) uniform Composite {
+    float u_opacity;
+    float u_over_white;
+};
+layout (binding = 1) uniform texture2D u_layer_texture;
+layout (binding = 2) uniform sampler u_layer_sampler;
+#define u_layer sampler2D(u_layer_texture, u_layer_sampler)
+#endif
+
+layout (location = 0) out vec4 o_frag;
+
+// layers store premultiplied colors, target and layer have the same size
+void main() {
+    vec4 color = u_opacity * texelFetch(u_layer, ivec2(gl_FragCoord.xy), 0);
+
+    // `BlendOp::Min` keeps the backdrop where the source is white, see `lanka.fs`
+    color.rgb += u_over_white * (1.0 - color.a);
+
+    o_frag = color;
+}
@@ -0,0 +1,14 @@
+#version 450 core
+
+#define GRR 1
+
+// fullscreen triangle
+void main() {
+#if GRR
+    const int id = int(gl_VertexID);
+#else
+    const int id = int(gl_VertexIndex);
+#endif
+    const vec2 uv = vec2((id << 1) & 2, id & 2);
+    gl_Position = vec4(2.0 * uv - 1.0, 0.0, 1.0);
+}
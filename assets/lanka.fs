@@ -3,6 +3,7 @@
 #define GRR 1
 
 const float FLOAT_MAX = 3.402823466e+38;
+const float PI = 3.14159265359;
 
 const uint PRIMITIVE_LINE = 0x1; // distance field generation (linear curve)
 const uint PRIMITIVE_QUADRATIC = 0x2; // quadratic curve
@@ -10,9 +11,27 @@ const uint PRIMITIVE_CIRCLE = 0x3;
 const uint PRIMITIVE_ARC = 0x4;
 const uint PRIMITIVE_RECT = 0x5;
 const uint PRIMITIVE_SHADOW_RECT = 0x6;
+const uint PRIMITIVE_ROUNDED_RECT = 0x7;
 
 const uint PRIMITIVE_FILL_COLOR = 0x10;
 const uint PRIMITIVE_FILL_LINEAR_GRADIENT = 0x11;
+const uint PRIMITIVE_FILL_RADIAL_GRADIENT = 0x12;
+const uint PRIMITIVE_FILL_SWEEP_GRADIENT = 0x13;
+const uint PRIMITIVE_FILL_IMAGE = 0x14;
+
+const uint PRIMITIVE_CLIP = 0x20;
+
+const uint SPREAD_PAD = 0x0;
+const uint SPREAD_REPEAT = 0x1;
+const uint SPREAD_REFLECT = 0x2;
+
+const uint SAMPLING_NEAREST = 0x0;
+const uint SAMPLING_LINEAR = 0x1;
+
+// flags in the upper bits of fill and clip primitives
+const uint PRIMITIVE_MASK = 0xFF;
+const uint FILL_RULE_EVEN_ODD = 0x100;
+const uint FILL_OVER_WHITE = 0x200;
 
 #if GRR
 layout (location = 0) uniform uint u_num_primitives;
@@ -36,6 +55,17 @@ layout (binding = 0, std430) readonly buffer SceneVertices {
 layout (binding = 1, std430) readonly buffer ScenePrimitives {
     uint primitives[];
 };
+layout (binding = 3, std430) readonly buffer SceneImages {
+    uvec4 image_rects[]; // atlas offset and extent
+};
+
+#if GRR
+layout (binding = 0) uniform sampler2D u_atlas;
+#else
+layout (binding = 4) uniform texture2D u_atlas_texture;
+layout (binding = 5) uniform sampler u_atlas_sampler;
+#define u_atlas sampler2D(u_atlas_texture, u_atlas_sampler)
+#endif
 
 float line_eval(float p0, float p1, float t) {
     return mix(p0, p1, t);
@@ -72,6 +102,56 @@ float erf(float x) {
     return s - s / (x * x);
 }
 
+// inset of a rounded rect edge at `x` caused by the corner arcs, and the slope factor for `cdf`
+vec2 rounded_edge(float x, float x0, float x1, float r0, float r1) {
+    float dx = 0.0;
+    float r = 0.0;
+    if (x < x0 + r0) {
+        dx = x0 + r0 - x;
+        r = r0;
+    } else if (x > x1 - r1) {
+        dx = x - (x1 - r1);
+        r = r1;
+    }
+
+    if (r == 0.0) {
+        return vec2(0.0, 1.0);
+    }
+
+    const float dy = sqrt(max(r * r - dx * dx, 0.0));
+    return vec2(r - dy, dy / r);
+}
+
+float gaussian(float x, float sigma) {
+    return exp(-(x * x) / (2.0 * sigma * sigma)) / (sqrt(2.0 * PI) * sigma);
+}
+
+// blurred rounded box along x at height `y`, see "Fast Rounded Rectangle Shadows" (Evan Wallace)
+float rounded_shadow_x(float x, float y, float sigma, float radius, vec2 half_extent) {
+    const float delta = min(half_extent.y - radius - abs(y), 0.0);
+    const float curved = half_extent.x - radius + sqrt(max(0.0, radius * radius - delta * delta));
+    const float norm = sqrt(0.5) / sigma;
+    return 0.5 * (erf((x + curved) * norm) - erf((x - curved) * norm));
+}
+
+// blurred rounded box evaluated at the origin, the y integral is sampled numerically
+float rounded_shadow(vec2 center, vec2 half_extent, float sigma, float radius) {
+    const vec2 p = -center;
+    const float low = p.y - half_extent.y;
+    const float high = p.y + half_extent.y;
+    const float start = clamp(-3.0 * sigma, low, high);
+    const float end = clamp(3.0 * sigma, low, high);
+    const float step = (end - start) / 4.0;
+
+    float y = start + 0.5 * step;
+    float value = 0.0;
+    for (int i = 0; i < 4; i++) {
+        value += rounded_shadow_x(p.x, p.y - y, sigma, radius, half_extent) * gaussian(y, sigma) * step;
+        y += step;
+    }
+    return value;
+}
+
 // float cdf(float x, float ddx) {
 //     return smoothstep(-0.8, 0.8, x * ddx);
 // }
@@ -81,6 +161,64 @@ float cdf(float x, float m) {
     return clamp(x*m + 0.5, 0.0, 1.0);
 }
 
+// resolve accumulated winding coverage according to the fill rule
+float fill_coverage(float coverage, uint primitive) {
+    if ((primitive & FILL_RULE_EVEN_ODD) != 0) {
+        return 1.0 - abs(1.0 - mod(coverage, 2.0));
+    }
+    return min(abs(coverage), 1.0);
+}
+
+// evaluate the gradient stop table at `t`, advances `base_vertex` past the table
+vec4 gradient_eval(inout uint base_vertex, float t) {
+    const uint header = vertices[base_vertex++];
+    const uint num_stops = header & 0xFFFF;
+    const uint spread = header >> 16;
+
+    switch (spread) {
+    case SPREAD_PAD: t = clamp(t, 0.0, 1.0); break;
+    case SPREAD_REPEAT: t = fract(t); break;
+    case SPREAD_REFLECT: t = 1.0 - abs(mod(t, 2.0) - 1.0); break;
+    }
+
+    float prev_offset = uintBitsToFloat(vertices[base_vertex]);
+    vec4 prev_color = unpackUnorm4x8(vertices[base_vertex + 1]);
+    vec4 color = prev_color;
+    for (uint s = 1; s < num_stops; s++) {
+        const float offset = uintBitsToFloat(vertices[base_vertex + 2 * s]);
+        const vec4 c = unpackUnorm4x8(vertices[base_vertex + 2 * s + 1]);
+        if (t > prev_offset) {
+            color = mix(prev_color, c, clamp((t - prev_offset) / max(offset - prev_offset, 1e-6), 0.0, 1.0));
+        }
+        prev_offset = offset;
+        prev_color = c;
+    }
+
+    base_vertex += 2 * num_stops;
+    return color;
+}
+
+// map texel coordinate into `[0, size)` according to the extend mode
+int image_extend(int x, int size, uint extend) {
+    switch (extend) {
+    case SPREAD_REPEAT: return ((x % size) + size) % size;
+    case SPREAD_REFLECT: {
+        const int m = ((x % (2 * size)) + 2 * size) % (2 * size);
+        return m < size ? m : 2 * size - 1 - m;
+    }
+    default: return clamp(x, 0, size - 1);
+    }
+}
+
+// fetch from the atlas, texels outside of the image rect are never touched
+// the atlas stores straight alpha, texels are premultiplied after sRGB decoding
+vec4 image_texel(uvec4 rect, ivec2 p, uint extend) {
+    const ivec2 size = ivec2(rect.zw);
+    const ivec2 q = ivec2(image_extend(p.x, size.x, extend), image_extend(p.y, size.y, extend));
+    const vec4 texel = texelFetch(u_atlas, ivec2(rect.xy) + q, 0);
+    return vec4(texel.rgb * texel.a, texel.a);
+}
+
 layout(location = 0) out vec4 o_frag;
 
 void main() {
@@ -89,14 +227,16 @@ void main() {
     vec2 dxdy = fwidth(tile_center);
     const vec2 unit = 1.0 / dxdy;
 
+    // premultiplied
     vec4 color = vec4(1.0, 0.0, 0.0, 1.0);
     float coverage = 0.0;
+    float clip = 1.0;
     float shadow = 0.0;
 
     uint base_vertex = f_curve_range.x;
     for (uint i = f_curve_range.y; i < f_curve_range.z; i++) {
         const uint primitive = primitives[i];
-        switch (primitive) {
+        switch (primitive & PRIMITIVE_MASK) {
         case PRIMITIVE_LINE: {
             const vec2 p0 = unpackHalf2x16(vertices[base_vertex++]) - tile_center;
             const vec2 p1 = unpackHalf2x16(vertices[base_vertex++]) - tile_center;
@@ -218,41 +358,151 @@ void main() {
             coverage += xx * cdf(dy1, 1.0);
         } break;
 
+        case PRIMITIVE_ROUNDED_RECT: {
+            const vec2 p0 = unpackHalf2x16(vertices[base_vertex++]) - tile_center;
+            const vec2 p1 = unpackHalf2x16(vertices[base_vertex++]) - tile_center;
+            const vec2 r_lower = unpackHalf2x16(vertices[base_vertex++]); // left, right
+            const vec2 r_upper = unpackHalf2x16(vertices[base_vertex++]); // right, left
+
+            const vec2 lower = min(p0, p1);
+            const vec2 upper = max(p0, p1);
+            const vec2 dir = sign(p1 - p0);
+
+            const float xx0 = clamp(lower.x, -0.5 * dxdy.x, 0.5 * dxdy.x);
+            const float xx1 = clamp(upper.x, -0.5 * dxdy.x, 0.5 * dxdy.x);
+            const float xx = (xx1 - xx0) * unit.x;
+
+            if (xx == 0.0) {
+                break;
+            }
+
+            const float x = 0.5 * (xx0 + xx1);
+            const vec2 e0 = rounded_edge(x, lower.x, upper.x, r_lower.x, r_lower.y);
+            const vec2 e1 = rounded_edge(x, lower.x, upper.x, r_upper.y, r_upper.x);
+
+            const float dy0 = (lower.y + e0.x) * unit.y;
+            const float dy1 = (upper.y - e1.x) * unit.y;
+
+            coverage += dir.x * dir.y * xx * (cdf(dy1, e1.y) - cdf(dy0, e0.y));
+        } break;
+
         case PRIMITIVE_SHADOW_RECT: {
             const vec2 p0 = unpackHalf2x16(vertices[base_vertex++]) - tile_center;
             const vec2 p1 = unpackHalf2x16(vertices[base_vertex++]) - tile_center;
             const float sigma = uintBitsToFloat(vertices[base_vertex++]);
+            const float radius = uintBitsToFloat(vertices[base_vertex++]);
+
+            if (radius > 0.0) {
+                const vec2 lower = min(p0, p1);
+                const vec2 upper = max(p0, p1);
+                const vec2 dir = sign(p1 - p0);
+                coverage += dir.x * dir.y * rounded_shadow(0.5 * (lower + upper), 0.5 * (upper - lower), sigma, radius);
+            } else {
+                // separable, exact
+                const float norm = sqrt(0.5) / sigma;
+                const float sy = 0.5 * (erf(p1.y * norm) - erf(p0.y * norm));
+                const float sx = 0.5 * (erf(p1.x * norm) - erf(p0.x * norm));
+
+                coverage += sy * sx;
+            }
+        } break;
 
-            const float norm = sqrt(0.5) / sigma;
-            const float sy = 0.5 * (erf(p1.y * norm) - erf(p0.y * norm));
-            const float sx = 0.5 * (erf(p1.x * norm) - erf(p0.x * norm));
-
-            coverage += sy * sx;
+        case PRIMITIVE_CLIP: {
+            // clip curves precede the draw, nested clips multiply
+            clip *= fill_coverage(coverage, primitive);
+            coverage = 0.0;
         } break;
 
         case PRIMITIVE_FILL_COLOR: {
             const vec4 brush = unpackUnorm4x8(vertices[base_vertex++]);
-            color.rgb = brush.rgb;
-
-            color.a = clamp(coverage, 0.0, 1.0);
+            color = clip * fill_coverage(coverage, primitive) * brush;
             coverage = 0.0;
         } break;
 
         case PRIMITIVE_FILL_LINEAR_GRADIENT: {
             const vec2 p0 = unpackHalf2x16(vertices[base_vertex++]);
-            const vec4 c0 = unpackUnorm4x8(vertices[base_vertex++]);
             const vec2 p1 = unpackHalf2x16(vertices[base_vertex++]);
-            const vec4 c1 = unpackUnorm4x8(vertices[base_vertex++]);
 
             const vec2 dir = p1 - p0;
-            const float t = clamp(dot(normalize(dir), f_pos_world - p0) / length(dir), 0.0, 1.0);
-            color.rgb = mix(c0, c1, t).rgb;
+            const float t = dot(normalize(dir), f_pos_world - p0) / length(dir);
+            color = clip * fill_coverage(coverage, primitive) * gradient_eval(base_vertex, t);
+            coverage = 0.0;
+        } break;
+
+        case PRIMITIVE_FILL_RADIAL_GRADIENT: {
+            const vec2 center = unpackHalf2x16(vertices[base_vertex++]);
+            const vec2 focal = unpackHalf2x16(vertices[base_vertex++]);
+            const float radius = uintBitsToFloat(vertices[base_vertex++]);
+
+            // find circle (lerp(focal, center, t), t * radius) passing through the sample
+            const vec2 d = center - focal;
+            const vec2 q = f_pos_world - focal;
+            const float a = dot(d, d) - radius * radius; // < 0, focal point inside
+            const float b = dot(q, d);
+            const float c = dot(q, q);
+            const float t = (b - sqrt(max(b * b - a * c, 0.0))) / a;
+            color = clip * fill_coverage(coverage, primitive) * gradient_eval(base_vertex, t);
+            coverage = 0.0;
+        } break;
+
+        case PRIMITIVE_FILL_SWEEP_GRADIENT: {
+            const vec2 center = unpackHalf2x16(vertices[base_vertex++]);
+            const float start_angle = uintBitsToFloat(vertices[base_vertex++]);
+            const float end_angle = uintBitsToFloat(vertices[base_vertex++]);
 
-            color.a = clamp(coverage, 0.0, 1.0);
+            // angle in [start_angle, start_angle + 2pi)
+            const vec2 q = f_pos_world - center;
+            const float angle = start_angle + mod(atan(q.y, q.x) - start_angle, 2.0 * PI);
+            const float t = (angle - start_angle) / (end_angle - start_angle);
+            color = clip * fill_coverage(coverage, primitive) * gradient_eval(base_vertex, t);
+            coverage = 0.0;
+        } break;
+        case PRIMITIVE_FILL_IMAGE: {
+            const uint image_id = vertices[base_vertex++];
+            const uint mode = vertices[base_vertex++];
+            const uint sampling = mode & 0xFF;
+            const uint extend = mode >> 8;
+
+            // world -> image texel space
+            const vec2 m0 = vec2(uintBitsToFloat(vertices[base_vertex]), uintBitsToFloat(vertices[base_vertex + 1]));
+            const vec2 m1 = vec2(uintBitsToFloat(vertices[base_vertex + 2]), uintBitsToFloat(vertices[base_vertex + 3]));
+            const vec2 t = vec2(uintBitsToFloat(vertices[base_vertex + 4]), uintBitsToFloat(vertices[base_vertex + 5]));
+            base_vertex += 6;
+
+            const vec2 p = m0 * f_pos_world.x + m1 * f_pos_world.y + t;
+
+            // unknown images and empty rects are transparent
+            uvec4 rect = uvec4(0);
+            if (image_id < uint(image_rects.length())) {
+                rect = image_rects[image_id];
+            }
+
+            vec4 texel = vec4(0.0);
+            if (rect.z == 0u || rect.w == 0u) {
+                // nothing to sample
+            } else if (sampling == SAMPLING_NEAREST) {
+                texel = image_texel(rect, ivec2(floor(p)), extend);
+            } else {
+                const vec2 s = p - 0.5;
+                const ivec2 i = ivec2(floor(s));
+                const vec2 f = fract(s);
+                texel = mix(
+                    mix(image_texel(rect, i, extend), image_texel(rect, i + ivec2(1, 0), extend), f.x),
+                    mix(image_texel(rect, i + ivec2(0, 1), extend), image_texel(rect, i + ivec2(1, 1), extend), f.x),
+                    f.y
+                );
+            }
+
+            color = clip * fill_coverage(coverage, primitive) * texel;
             coverage = 0.0;
         } break;
         }
     }
 
+    // `BlendOp::Min` keeps the backdrop where the source is white
+    if (f_curve_range.y < f_curve_range.z && (primitives[f_curve_range.z - 1] & FILL_OVER_WHITE) != 0) {
+        color.rgb += 1.0 - color.a;
+    }
+
     o_frag = color; // vec4(shadow, shadow, shadow, 1.0);
 }
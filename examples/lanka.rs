@@ -35,30 +35,22 @@ fn main() -> Result<(), Box<dyn Error>> {
             for segment in &shape.segments {
                 match segment {
                     Segment::Line(line) => {
-                        path = path.move_to(glm::vec2(
-                            line.p[0].x - bbox.min.x,
-                            line.p[0].y + bbox.max.y,
-                        ));
-                        path = path.line_to(glm::vec2(
-                            line.p[1].x - bbox.min.x,
-                            line.p[1].y + bbox.max.y,
-                        ));
+                        path = path.move_to(glm::vec2(line.p[0].x, line.p[0].y));
+                        path = path.line_to(glm::vec2(line.p[1].x, line.p[1].y));
                     }
                     Segment::Curve(curve) => {
-                        path = path.move_to(glm::vec2(
-                            curve.p[0].x - bbox.min.x,
-                            curve.p[0].y + bbox.max.y,
-                        ));
+                        path = path.move_to(glm::vec2(curve.p[0].x, curve.p[0].y));
                         path = path.quad_to(
-                            glm::vec2(curve.p[1].x - bbox.min.x, curve.p[1].y + bbox.max.y),
-                            glm::vec2(curve.p[2].x - bbox.min.x, curve.p[2].y + bbox.max.y),
+                            glm::vec2(curve.p[1].x, curve.p[1].y),
+                            glm::vec2(curve.p[2].x, curve.p[2].y),
                         );
                     }
                 }
             }
         }
 
-        let curves = path.monotonize().fill().finish();
+        let to_curve = grr_2d::Transform::translate(glm::vec2(-bbox.min.x, bbox.max.y));
+        let curves = path.transform(&to_curve).monotonize().fill().finish();
 
         let rect = grr_2d::Rect {
             offset_local: glm::vec2(pos.x + bbox.min.x as f32, pos.y + bbox.min.y as f32),
@@ -76,37 +68,37 @@ fn main() -> Result<(), Box<dyn Error>> {
         gpu_data.extend(
             &curves,
             rect,
-            &grr_2d::Brush::Color([0, 0, 0, 255])
+            &grr_2d::Brush::Color([0, 0, 0, 255]),
             // &grr_2d::Brush::LinearGradient {
-            //     stop0: grr_2d::GradientStop {
-            //         position: glm::vec2(0.0, 80.0),
-            //         color: [255, 100, 0, 255],
-            //     },
-            //     stop1: grr_2d::GradientStop {
-            //         position: glm::vec2(0.0, 150.0),
-            //         color: [255, 0, 70, 255],
-            //     },
+            //     start: glm::vec2(0.0, 80.0),
+            //     end: glm::vec2(0.0, 150.0),
+            //     stops: vec![
+            //         grr_2d::GradientStop {
+            //             offset: 0.0,
+            //             color: [255, 100, 0, 255],
+            //         },
+            //         grr_2d::GradientStop {
+            //             offset: 1.0,
+            //             color: [255, 0, 70, 255],
+            //         },
+            //     ],
+            //     spread: grr_2d::SpreadMode::Pad,
             // },
+            grr_2d::FillRule::NonZero,
         );
     }
 
-    let box_path = grr_2d::PathBuilder::new();
+    let box_path = grr_2d::PathBuilder::from_svg_path("M 0 0 Q 100 200 140 100")?;
     let box_path = box_path
-        .move_to(glm::vec2(0.0, 0.0))
-        .quad_to(glm::vec2(100.0, 200.0), glm::vec2(140.0, 100.0))
         // .move_to(glm::vec2(-30.0, 40.0))
         // .quad_to(glm::vec2(-20.0, 60.0), glm::vec2(20.0, 70.0))
         // .line_to(glm::vec2(50.0, 50.0))
         // .line_to(glm::vec2(30.0, 0.0))
         .monotonize()
-        .stroke(
-            20.0,
-            (
-                grr_2d::CurveCap::Butt,
-                grr_2d::CurveJoin::Round,
-                grr_2d::CurveCap::Butt,
-            ),
-        );
+        .stroke(&grr_2d::StrokeStyle {
+            join: grr_2d::CurveJoin::Round,
+            ..grr_2d::StrokeStyle::new(40.0)
+        });
     let box_aabb = grr_2d::Aabb::from_curves(&box_path);
     gpu_data.extend(
         &box_path,
@@ -118,6 +110,7 @@ fn main() -> Result<(), Box<dyn Error>> {
         })
         .extrude(10.0)),
         &grr_2d::Brush::Color([255, 0, 0, 255]),
+        grr_2d::FillRule::NonZero,
     );
 
     let rect_path = [grr_2d::Curve::Rect {
@@ -135,7 +128,8 @@ fn main() -> Result<(), Box<dyn Error>> {
         }
         .extrude(20.0),
         &grr_2d::Brush::Color([100, 100, 200, 255]),
+        grr_2d::FillRule::NonZero,
     );
 
-    unsafe { grr_2d::run("lanka", || { gpu_data.clone() } ) }
+    unsafe { grr_2d::run("lanka", &grr_2d::ImageAtlas::new(1, 1), || { gpu_data.clone() } ) }
 }
@@ -224,6 +224,7 @@ impl LayoutWidgets {
             }
             .extrude(2.0),
             &grr_2d::Brush::Color([c, c, c, 255]),
+            grr_2d::FillRule::NonZero,
         );
 
         // dbg!((id, offset, widget.rect.extent));
@@ -472,7 +473,7 @@ fn main() -> Result<(), Box<dyn Error>> {
     let mut ui = Ui::new();
 
     unsafe {
-        grr_2d::run("layout", || {
+        grr_2d::run("layout", &grr_2d::ImageAtlas::new(1, 1), || {
             let mut gpu_data = grr_2d::GpuData::new();
 
             let mut root = ui.begin_phase(Phase::Layout {
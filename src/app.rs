@@ -1,9 +1,381 @@
-use crate::{GpuData, Viewport, FrameTime};
-use glutin::dpi::LogicalSize;
+use crate::{
+    glm, BlendChannel, BlendFactor, BlendMode, BlendOp, DrawCommand, FrameTime, GpuData,
+    ImageAtlas, Viewport,
+};
+use glutin::dpi::{LogicalSize, PhysicalSize};
 use glutin::ElementState;
 use std::error::Error;
 
-pub unsafe fn run<F>(name: &'static str, mut update: F) -> Result<(), Box<dyn Error>>
+fn grr_blend_channel(channel: BlendChannel) -> grr::BlendChannel {
+    let factor = |factor| match factor {
+        BlendFactor::Zero => grr::BlendFactor::Zero,
+        BlendFactor::One => grr::BlendFactor::One,
+        BlendFactor::OneMinusSrcColor => grr::BlendFactor::OneMinusSrcColor,
+        BlendFactor::SrcAlpha => grr::BlendFactor::SrcAlpha,
+        BlendFactor::OneMinusSrcAlpha => grr::BlendFactor::OneMinusSrcAlpha,
+        BlendFactor::DstColor => grr::BlendFactor::DstColor,
+        BlendFactor::DstAlpha => grr::BlendFactor::DstAlpha,
+        BlendFactor::OneMinusDstAlpha => grr::BlendFactor::OneMinusDstAlpha,
+    };
+
+    grr::BlendChannel {
+        src_factor: factor(channel.src),
+        dst_factor: factor(channel.dst),
+        blend_op: match channel.op {
+            BlendOp::Add => grr::BlendOp::Add,
+            BlendOp::Min => grr::BlendOp::Min,
+            BlendOp::Max => grr::BlendOp::Max,
+        },
+    }
+}
+
+// Draws and layers are premultiplied.
+fn grr_color_blend(blend_mode: BlendMode) -> grr::ColorBlend {
+    let state = blend_mode.state();
+    grr::ColorBlend {
+        attachments: vec![grr::ColorBlendAttachment {
+            blend_enable: true,
+            color: grr_blend_channel(state.color),
+            alpha: grr_blend_channel(state.alpha),
+        }],
+    }
+}
+
+// Offscreen target for layers, linear half floats holding premultiplied colors.
+unsafe fn create_layer_target(
+    grr: &grr::Device,
+    width: u32,
+    height: u32,
+) -> Result<(grr::Image, grr::ImageView, grr::Framebuffer), Box<dyn Error>> {
+    let (image, view) = grr.create_image_and_view(
+        grr::ImageType::D2 {
+            width,
+            height,
+            layers: 1,
+            samples: 1,
+        },
+        grr::Format::R16G16B16A16_SFLOAT,
+        1,
+    )?;
+    let framebuffer = grr.create_framebuffer()?;
+    grr.bind_attachments(
+        framebuffer,
+        &[(grr::Attachment::Color(0), grr::AttachmentView::Image(view))],
+    );
+
+    Ok((image, view, framebuffer))
+}
+
+// Pipelines and static resources shared by the window loop and headless rendering.
+struct Renderer {
+    vertex_array: grr::VertexArray,
+    pipeline_raster: grr::Pipeline,
+    pipeline_composite: grr::Pipeline,
+    // grows with the max. layer depth, recreated if the target size changes
+    layer_targets: Vec<(grr::Image, grr::ImageView, grr::Framebuffer)>,
+    layer_size: (u32, u32),
+    atlas_image: grr::Image,
+    atlas_view: grr::ImageView,
+    atlas_rects: grr::Buffer,
+    num_atlas_rects: usize,
+}
+
+impl Renderer {
+    unsafe fn new(grr: &grr::Device, atlas: &ImageAtlas) -> Result<Self, Box<dyn Error>> {
+        let vertex_array = grr.create_vertex_array(&[
+            grr::VertexAttributeDesc {
+                location: 0,
+                binding: 0,
+                format: grr::VertexFormat::Xy32Float, // pos world
+                offset: 0,
+            },
+            grr::VertexAttributeDesc {
+                location: 1,
+                binding: 0,
+                format: grr::VertexFormat::Xy32Float, // pos curve
+                offset: 2 * std::mem::size_of::<f32>() as u32,
+            },
+            grr::VertexAttributeDesc {
+                location: 2,
+                binding: 1,
+                format: grr::VertexFormat::Xyz32Uint, // curve range
+                offset: 0,
+            },
+        ])?;
+
+        let shader_vs = grr.create_shader(
+            grr::ShaderStage::Vertex,
+            include_bytes!("../assets/lanka.vs"),
+        )?;
+        let shader_fs = grr.create_shader(
+            grr::ShaderStage::Fragment,
+            include_bytes!("../assets/lanka.fs"),
+        )?;
+        let pipeline_raster = grr.create_graphics_pipeline(grr::VertexPipelineDesc {
+            vertex_shader: shader_vs,
+            geometry_shader: None,
+            tessellation_evaluation_shader: None,
+            tessellation_control_shader: None,
+            fragment_shader: Some(shader_fs),
+        })?;
+
+        let composite_vs = grr.create_shader(
+            grr::ShaderStage::Vertex,
+            include_bytes!("../assets/composite.vs"),
+        )?;
+        let composite_fs = grr.create_shader(
+            grr::ShaderStage::Fragment,
+            include_bytes!("../assets/composite.fs"),
+        )?;
+        let pipeline_composite = grr.create_graphics_pipeline(grr::VertexPipelineDesc {
+            vertex_shader: composite_vs,
+            geometry_shader: None,
+            tessellation_evaluation_shader: None,
+            tessellation_control_shader: None,
+            fragment_shader: Some(composite_fs),
+        })?;
+
+        // image atlas, static for the lifetime of the renderer
+        let (atlas_image, atlas_view) = grr.create_image_and_view(
+            grr::ImageType::D2 {
+                width: atlas.width,
+                height: atlas.height,
+                layers: 1,
+                samples: 1,
+            },
+            grr::Format::R8G8B8A8_SRGB,
+            1,
+        )?;
+        grr.copy_host_to_image(
+            &atlas.data,
+            atlas_image,
+            grr::HostImageCopy {
+                host_layout: grr::MemoryLayout {
+                    base_format: grr::BaseFormat::RGBA,
+                    format_layout: grr::FormatLayout::U8,
+                    row_length: atlas.width,
+                    image_height: atlas.height,
+                    alignment: 4,
+                },
+                image_subresource: grr::SubresourceLayers {
+                    level: 0,
+                    layers: 0..1,
+                },
+                image_offset: grr::Offset { x: 0, y: 0, z: 0 },
+                image_extent: grr::Extent {
+                    width: atlas.width,
+                    height: atlas.height,
+                    depth: 1,
+                },
+            },
+        );
+        let gpu_rects = atlas.gpu_rects();
+        let atlas_rects =
+            grr.create_buffer_from_host(grr::as_u8_slice(&gpu_rects), grr::MemoryFlags::empty())?;
+
+        Ok(Renderer {
+            vertex_array,
+            pipeline_raster,
+            pipeline_composite,
+            layer_targets: Vec::new(),
+            layer_size: (0, 0),
+            atlas_image,
+            atlas_view,
+            atlas_rects,
+            num_atlas_rects: gpu_rects.len(),
+        })
+    }
+
+    // Clears `target` to `clear_color` and draws `gpu_data` into it.
+    unsafe fn draw(
+        &mut self,
+        grr: &grr::Device,
+        gpu_data: &GpuData,
+        viewport: &Viewport,
+        target: grr::Framebuffer,
+        (w, h): (u32, u32),
+        clear_color: [f32; 4],
+    ) -> Result<(), Box<dyn Error>> {
+        // `composite.fs` fetches layer texels at the fragment position
+        if self.layer_size != (w, h) {
+            self.delete_layer_targets(grr);
+            self.layer_size = (w, h);
+        }
+
+        let gpu_vertices = grr.create_buffer_from_host(
+            grr::as_u8_slice(&gpu_data.vertices),
+            grr::MemoryFlags::empty(),
+        )?;
+        let gpu_bbox = grr
+            .create_buffer_from_host(grr::as_u8_slice(&gpu_data.bbox), grr::MemoryFlags::empty())?;
+        let gpu_primitives = grr.create_buffer_from_host(
+            grr::as_u8_slice(&gpu_data.primitives),
+            grr::MemoryFlags::empty(),
+        )?;
+        let gpu_curve_ranges = grr.create_buffer_from_host(
+            grr::as_u8_slice(&gpu_data.curve_ranges),
+            grr::MemoryFlags::empty(),
+        )?;
+
+        grr.bind_vertex_array(self.vertex_array);
+        grr.bind_vertex_buffers(
+            self.vertex_array,
+            0,
+            &[
+                grr::VertexBufferView {
+                    buffer: gpu_bbox,
+                    offset: 0,
+                    stride: (std::mem::size_of::<f32>() * 4) as _,
+                    input_rate: grr::InputRate::Vertex,
+                },
+                grr::VertexBufferView {
+                    buffer: gpu_curve_ranges,
+                    offset: 0,
+                    stride: (std::mem::size_of::<u32>() * 3) as _,
+                    input_rate: grr::InputRate::Vertex,
+                },
+            ],
+        );
+
+        grr.set_viewport(
+            0,
+            &[grr::Viewport {
+                x: 0.0,
+                y: 0.0,
+                w: w as _,
+                h: h as _,
+                n: 0.0,
+                f: 1.0,
+            }],
+        );
+        grr.set_scissor(
+            0,
+            &[grr::Region {
+                x: 0,
+                y: 0,
+                w: w as _,
+                h: h as _,
+            }],
+        );
+
+        grr.bind_uniform_constants(
+            self.pipeline_raster,
+            0,
+            &[
+                grr::Constant::U32(gpu_data.primitives.len() as _), // primitives
+                grr::Constant::Vec4(viewport.get_rect()),           // viewport
+                grr::Constant::Vec2([w as f32, h as f32]),          // primitives
+            ],
+        );
+        grr.bind_storage_buffers(
+            0,
+            &[
+                grr::BufferRange {
+                    buffer: gpu_vertices,
+                    offset: 0,
+                    size: (std::mem::size_of::<u32>() * gpu_data.vertices.len()) as _,
+                },
+                grr::BufferRange {
+                    buffer: gpu_primitives,
+                    offset: 0,
+                    size: (std::mem::size_of::<u32>() * gpu_data.primitives.len()) as _,
+                },
+            ],
+        );
+        grr.bind_storage_buffers(
+            3,
+            &[grr::BufferRange {
+                buffer: self.atlas_rects,
+                offset: 0,
+                size: (std::mem::size_of::<u32>() * self.num_atlas_rects) as _,
+            }],
+        );
+        grr.bind_image_views(0, &[self.atlas_view]);
+
+        grr.bind_framebuffer(target);
+        grr.clear_attachment(target, grr::ClearAttachment::ColorFloat(0, clear_color));
+
+        let mut layers = Vec::new();
+        for command in gpu_data.balanced_commands() {
+            match command {
+                DrawCommand::Draw {
+                    start,
+                    end,
+                    blend_mode,
+                } => {
+                    grr.bind_pipeline(self.pipeline_raster);
+                    grr.bind_color_blend_state(&grr_color_blend(blend_mode));
+                    grr.draw(grr::Primitive::Triangles, start..end, 0..1);
+                }
+                DrawCommand::PushLayer {
+                    opacity,
+                    blend_mode,
+                } => {
+                    let depth = layers.len();
+                    if self.layer_targets.len() == depth {
+                        self.layer_targets.push(create_layer_target(grr, w, h)?);
+                    }
+                    let (_, _, framebuffer) = self.layer_targets[depth];
+                    grr.bind_framebuffer(framebuffer);
+                    grr.clear_attachment(
+                        framebuffer,
+                        grr::ClearAttachment::ColorFloat(0, [0.0, 0.0, 0.0, 0.0]),
+                    );
+                    layers.push((opacity, blend_mode));
+                }
+                DrawCommand::PopLayer => {
+                    let (opacity, blend_mode) = layers.pop().unwrap();
+                    let depth = layers.len();
+                    let (_, layer_view, _) = self.layer_targets[depth];
+                    let parent = if depth == 0 {
+                        target
+                    } else {
+                        self.layer_targets[depth - 1].2
+                    };
+
+                    grr.bind_framebuffer(parent);
+                    grr.bind_pipeline(self.pipeline_composite);
+                    grr.bind_color_blend_state(&grr_color_blend(blend_mode));
+                    grr.bind_uniform_constants(
+                        self.pipeline_composite,
+                        0,
+                        &[
+                            grr::Constant::F32(opacity),
+                            grr::Constant::F32(if blend_mode.over_white() { 1.0 } else { 0.0 }),
+                        ],
+                    );
+                    grr.bind_image_views(1, &[layer_view]);
+                    grr.draw(grr::Primitive::Triangles, 0..3, 0..1);
+                }
+            }
+        }
+
+        grr.delete_buffers(&[gpu_vertices, gpu_bbox, gpu_primitives, gpu_curve_ranges]);
+
+        Ok(())
+    }
+
+    unsafe fn delete_layer_targets(&mut self, grr: &grr::Device) {
+        for (image, view, framebuffer) in self.layer_targets.drain(..) {
+            grr.delete_framebuffer(framebuffer);
+            grr.delete_image_view(view);
+            grr.delete_image(image);
+        }
+    }
+
+    unsafe fn delete(mut self, grr: &grr::Device) {
+        self.delete_layer_targets(grr);
+        grr.delete_buffer(self.atlas_rects);
+        grr.delete_image_view(self.atlas_view);
+        grr.delete_image(self.atlas_image);
+    }
+}
+
+pub unsafe fn run<F>(
+    name: &'static str,
+    atlas: &ImageAtlas,
+    mut update: F,
+) -> Result<(), Box<dyn Error>>
 where
     F: FnMut() -> GpuData,
 {
@@ -38,58 +410,7 @@ where
             flags: grr::DebugReport::FULL,
         },
     );
-    let vertex_array = grr.create_vertex_array(&[
-        grr::VertexAttributeDesc {
-            location: 0,
-            binding: 0,
-            format: grr::VertexFormat::Xy32Float, // pos world
-            offset: 0,
-        },
-        grr::VertexAttributeDesc {
-            location: 1,
-            binding: 0,
-            format: grr::VertexFormat::Xy32Float, // pos curve
-            offset: 2 * std::mem::size_of::<f32>() as u32,
-        },
-        grr::VertexAttributeDesc {
-            location: 2,
-            binding: 1,
-            format: grr::VertexFormat::Xyz32Uint, // curve range
-            offset: 0,
-        },
-    ])?;
-
-    let color_blend = grr::ColorBlend {
-        attachments: vec![grr::ColorBlendAttachment {
-            blend_enable: true,
-            color: grr::BlendChannel {
-                src_factor: grr::BlendFactor::SrcAlpha,
-                dst_factor: grr::BlendFactor::OneMinusSrcAlpha,
-                blend_op: grr::BlendOp::Add,
-            },
-            alpha: grr::BlendChannel {
-                src_factor: grr::BlendFactor::SrcAlpha,
-                dst_factor: grr::BlendFactor::OneMinusSrcAlpha,
-                blend_op: grr::BlendOp::Add,
-            },
-        }],
-    };
-
-    let shader_vs = grr.create_shader(
-        grr::ShaderStage::Vertex,
-        include_bytes!("../assets/lanka.vs"),
-    )?;
-    let shader_fs = grr.create_shader(
-        grr::ShaderStage::Fragment,
-        include_bytes!("../assets/lanka.fs"),
-    )?;
-    let pipeline_raster = grr.create_graphics_pipeline(grr::VertexPipelineDesc {
-        vertex_shader: shader_vs,
-        geometry_shader: None,
-        tessellation_evaluation_shader: None,
-        tessellation_control_shader: None,
-        fragment_shader: Some(shader_fs),
-    })?;
+    let mut renderer = Renderer::new(&grr, atlas)?;
 
     let mut running = true;
 
@@ -161,98 +482,16 @@ where
 
         let gpu_data = update();
 
-        let gpu_vertices = grr.create_buffer_from_host(
-            grr::as_u8_slice(&gpu_data.vertices),
-            grr::MemoryFlags::empty(),
-        )?;
-        let gpu_bbox = grr
-            .create_buffer_from_host(grr::as_u8_slice(&gpu_data.bbox), grr::MemoryFlags::empty())?;
-        let gpu_primitives = grr.create_buffer_from_host(
-            grr::as_u8_slice(&gpu_data.primitives),
-            grr::MemoryFlags::empty(),
-        )?;
-        let gpu_curve_ranges = grr.create_buffer_from_host(
-            grr::as_u8_slice(&gpu_data.curve_ranges),
-            grr::MemoryFlags::empty(),
-        )?;
-
-        grr.bind_vertex_array(vertex_array);
-        grr.bind_vertex_buffers(
-            vertex_array,
-            0,
-            &[
-                grr::VertexBufferView {
-                    buffer: gpu_bbox,
-                    offset: 0,
-                    stride: (std::mem::size_of::<f32>() * 4) as _,
-                    input_rate: grr::InputRate::Vertex,
-                },
-                grr::VertexBufferView {
-                    buffer: gpu_curve_ranges,
-                    offset: 0,
-                    stride: (std::mem::size_of::<u32>() * 3) as _,
-                    input_rate: grr::InputRate::Vertex,
-                },
-            ],
-        );
-
-        grr.set_viewport(
-            0,
-            &[grr::Viewport {
-                x: 0.0,
-                y: 0.0,
-                w: w as _,
-                h: h as _,
-                n: 0.0,
-                f: 1.0,
-            }],
-        );
-        grr.set_scissor(
-            0,
-            &[grr::Region {
-                x: 0,
-                y: 0,
-                w: w as _,
-                h: h as _,
-            }],
-        );
-
-        grr.bind_pipeline(pipeline_raster);
-        grr.bind_color_blend_state(&color_blend);
-        grr.bind_uniform_constants(
-            pipeline_raster,
-            0,
-            &[
-                grr::Constant::U32(gpu_data.primitives.len() as _), // primitives
-                grr::Constant::Vec4(viewport.get_rect()),           // viewport
-                grr::Constant::Vec2([w as f32, h as f32]),          // primitives
-            ],
-        );
-        grr.bind_storage_buffers(
-            0,
-            &[
-                grr::BufferRange {
-                    buffer: gpu_vertices,
-                    offset: 0,
-                    size: (std::mem::size_of::<u32>() * gpu_data.vertices.len()) as _,
-                },
-                grr::BufferRange {
-                    buffer: gpu_primitives,
-                    offset: 0,
-                    size: (std::mem::size_of::<u32>() * gpu_data.primitives.len()) as _,
-                },
-            ],
-        );
-
-        grr.clear_attachment(
-            grr::Framebuffer::DEFAULT,
-            grr::ClearAttachment::ColorFloat(0, [1.0, 1.0, 1.0, 0.0]),
-        );
-
         grr.write_timestamp(query[0]);
 
-        let num_vertices = gpu_data.bbox.len() as u32 / 4;
-        grr.draw(grr::Primitive::Triangles, 0..num_vertices, 0..1);
+        renderer.draw(
+            &grr,
+            &gpu_data,
+            &viewport,
+            grr::Framebuffer::DEFAULT,
+            (w as _, h as _),
+            [1.0, 1.0, 1.0, 1.0],
+        )?;
 
         grr.write_timestamp(query[1]);
 
@@ -262,9 +501,99 @@ where
         avg_frametime_gpu.update((t1 - t0) as f32 / 1_000_000_000.0f32);
 
         window.swap_buffers()?;
-
-        grr.delete_buffers(&[gpu_vertices, gpu_bbox, gpu_primitives, gpu_curve_ranges]);
     }
 
+    renderer.delete(&grr);
+
     Ok(())
 }
+
+/// Render `gpu_data` into an offscreen framebuffer of `size` and read back the pixels.
+///
+/// Image brushes sample an empty atlas, see `render_to_image_with_atlas`.
+pub fn render_to_image(
+    gpu_data: &GpuData,
+    viewport: &Viewport,
+    size: (u32, u32),
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    render_to_image_with_atlas(gpu_data, &ImageAtlas::new(1, 1), viewport, size)
+}
+
+/// Render `gpu_data` with the images of `atlas` into an offscreen framebuffer of `size`.
+///
+/// Returns RGBA8 rows from top to bottom, sRGB encoded with straight alpha over a transparent
+/// background, the same layout as `cpu::render`. A headless GL 4.5 context is created for each
+/// call, software implementations like Mesa llvmpipe (`LIBGL_ALWAYS_SOFTWARE=1`) work as well.
+/// On Linux the context still needs a display connection, e.g. Xvfb on servers, an error is
+/// returned if none is available. Empty sizes are rejected as well.
+pub fn render_to_image_with_atlas(
+    gpu_data: &GpuData,
+    atlas: &ImageAtlas,
+    viewport: &Viewport,
+    (width, height): (u32, u32),
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    if width == 0 || height == 0 {
+        return Err(format!("invalid image size {}x{}", width, height).into());
+    }
+
+    // glutin panics instead of returning an error if there is no display to connect to
+    let events_loop = std::panic::catch_unwind(glutin::EventsLoop::new)
+        .map_err(|_| "failed to create an event loop, no display available")?;
+    let context = glutin::ContextBuilder::new()
+        .build_headless(&events_loop, PhysicalSize::new(width as f64, height as f64))?;
+
+    unsafe {
+        let context = context.make_current().map_err(|(_, err)| err)?;
+        let grr = grr::Device::new(
+            |symbol| context.get_proc_address(symbol) as *const _,
+            grr::Debug::Disable,
+        );
+
+        // linear target, encoded on readback
+        let (image, view, framebuffer) = create_layer_target(&grr, width, height)?;
+        let mut renderer = Renderer::new(&grr, atlas)?;
+        renderer.draw(
+            &grr,
+            gpu_data,
+            viewport,
+            framebuffer,
+            (width, height),
+            [0.0, 0.0, 0.0, 0.0],
+        )?;
+
+        let mut texels = vec![0.0f32; 4 * (width * height) as usize];
+        grr.bind_framebuffer(framebuffer);
+        grr.copy_attachment_to_host(
+            grr::Region {
+                x: 0,
+                y: 0,
+                w: width as _,
+                h: height as _,
+            },
+            grr::MemoryLayout {
+                base_format: grr::BaseFormat::RGBA,
+                format_layout: grr::FormatLayout::F32,
+                row_length: width,
+                image_height: height,
+                alignment: 4,
+            },
+            &mut texels,
+        );
+
+        renderer.delete(&grr);
+        grr.delete_framebuffer(framebuffer);
+        grr.delete_image_view(view);
+        grr.delete_image(image);
+
+        // GL rows start at the bottom
+        let mut pixels = Vec::with_capacity(texels.len());
+        for row in texels.chunks(4 * width as usize).rev() {
+            for texel in row.chunks(4) {
+                let color = glm::vec4(texel[0], texel[1], texel[2], texel[3]);
+                pixels.extend_from_slice(&crate::cpu::encode_pixel(color));
+            }
+        }
+
+        Ok(pixels)
+    }
+}
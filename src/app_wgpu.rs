@@ -1,4 +1,8 @@
-use crate::{GpuData, Viewport, FrameTime};
+use crate::{
+    BlendChannel, BlendFactor, BlendMode, BlendOp, DrawCommand, FrameTime, GpuData, ImageAtlas,
+    Viewport,
+};
+use std::collections::HashMap;
 use std::error::Error;
 use wgpu::vertex_attr_array;
 use winit::dpi::LogicalSize;
@@ -15,7 +19,151 @@ struct Locals {
     _pad: u32,
 }
 
-pub unsafe fn run_wgpu(name: &'static str, gpu_data: GpuData) -> Result<(), Box<dyn Error>> {
+#[repr(C)]
+#[derive(Copy, Clone, AsBytes)]
+struct CompositeLocals {
+    opacity: f32,
+    over_white: f32,
+    _pad: [u32; 2],
+}
+
+// Offscreen target for layers, linear half floats holding premultiplied colors.
+const LAYER_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+const SWAP_CHAIN_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Bgra8UnormSrgb;
+
+// Buffer to texture copies require row pitches aligned to 256 bytes (D3D12, Metal).
+const ROW_PITCH_ALIGNMENT: usize = 256;
+
+const TRANSPARENT: wgpu::Color = wgpu::Color {
+    r: 0.0,
+    g: 0.0,
+    b: 0.0,
+    a: 0.0,
+};
+
+fn wgpu_blend_descriptor(channel: BlendChannel) -> wgpu::BlendDescriptor {
+    let factor = |factor| match factor {
+        BlendFactor::Zero => wgpu::BlendFactor::Zero,
+        BlendFactor::One => wgpu::BlendFactor::One,
+        BlendFactor::OneMinusSrcColor => wgpu::BlendFactor::OneMinusSrcColor,
+        BlendFactor::SrcAlpha => wgpu::BlendFactor::SrcAlpha,
+        BlendFactor::OneMinusSrcAlpha => wgpu::BlendFactor::OneMinusSrcAlpha,
+        BlendFactor::DstColor => wgpu::BlendFactor::DstColor,
+        BlendFactor::DstAlpha => wgpu::BlendFactor::DstAlpha,
+        BlendFactor::OneMinusDstAlpha => wgpu::BlendFactor::OneMinusDstAlpha,
+    };
+
+    wgpu::BlendDescriptor {
+        src_factor: factor(channel.src),
+        dst_factor: factor(channel.dst),
+        operation: match channel.op {
+            BlendOp::Add => wgpu::BlendOperation::Add,
+            BlendOp::Min => wgpu::BlendOperation::Min,
+            BlendOp::Max => wgpu::BlendOperation::Max,
+        },
+    }
+}
+
+// Draws and layers are premultiplied.
+fn wgpu_color_state(
+    format: wgpu::TextureFormat,
+    blend_mode: BlendMode,
+) -> wgpu::ColorStateDescriptor {
+    let state = blend_mode.state();
+    wgpu::ColorStateDescriptor {
+        format,
+        color_blend: wgpu_blend_descriptor(state.color),
+        alpha_blend: wgpu_blend_descriptor(state.alpha),
+        write_mask: wgpu::ColorWrite::ALL,
+    }
+}
+
+fn begin_pass<'a>(
+    encoder: &'a mut wgpu::CommandEncoder,
+    target: &'a wgpu::TextureView,
+    load_op: wgpu::LoadOp,
+    clear_color: wgpu::Color,
+) -> wgpu::RenderPass<'a> {
+    encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+            attachment: target,
+            resolve_target: None,
+            load_op,
+            store_op: wgpu::StoreOp::Store,
+            clear_color,
+        }],
+        depth_stencil_attachment: None,
+    })
+}
+
+// One offscreen target per nesting level of layers, sized like the swap chain.
+fn create_layer_targets(
+    device: &wgpu::Device,
+    width: u32,
+    height: u32,
+    num_layers: usize,
+) -> Vec<(wgpu::Texture, wgpu::TextureView)> {
+    (0..num_layers)
+        .map(|_| {
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth: 1,
+                },
+                array_layer_count: 1,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: LAYER_FORMAT,
+                usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::OUTPUT_ATTACHMENT,
+            });
+            let view = texture.create_default_view();
+            (texture, view)
+        })
+        .collect()
+}
+
+// Composite bind groups reference the layer targets and are recreated with them.
+fn create_composite_bind_groups(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    composites: &[(wgpu::RenderPipeline, wgpu::Buffer, usize)],
+    layer_targets: &[(wgpu::Texture, wgpu::TextureView)],
+    sampler: &wgpu::Sampler,
+) -> Vec<wgpu::BindGroup> {
+    composites
+        .iter()
+        .map(|(_, composite_locals, layer)| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout,
+                bindings: &[
+                    wgpu::Binding {
+                        binding: 0,
+                        resource: wgpu::BindingResource::Buffer {
+                            buffer: composite_locals,
+                            range: 0..std::mem::size_of::<CompositeLocals>() as _,
+                        },
+                    },
+                    wgpu::Binding {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(&layer_targets[*layer].1),
+                    },
+                    wgpu::Binding {
+                        binding: 2,
+                        resource: wgpu::BindingResource::Sampler(sampler),
+                    },
+                ],
+            })
+        })
+        .collect()
+}
+
+pub unsafe fn run_wgpu(
+    name: &'static str,
+    atlas: &ImageAtlas,
+    gpu_data: GpuData,
+) -> Result<(), Box<dyn Error>> {
     let mut event_loop = winit::event_loop::EventLoop::new();
     let wb = winit::window::WindowBuilder::new()
         .with_title(name)
@@ -46,7 +194,7 @@ pub unsafe fn run_wgpu(name: &'static str, gpu_data: GpuData) -> Result<(), Box<
 
     let mut sc_desc = wgpu::SwapChainDescriptor {
         usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT,
-        format: wgpu::TextureFormat::Bgra8UnormSrgb,
+        format: SWAP_CHAIN_FORMAT,
         width: size.width,
         height: size.height,
         present_mode: wgpu::PresentMode::Vsync,
@@ -89,6 +237,27 @@ pub unsafe fn run_wgpu(name: &'static str, gpu_data: GpuData) -> Result<(), Box<
                 visibility: wgpu::ShaderStage::VERTEX | wgpu::ShaderStage::FRAGMENT,
                 ty: wgpu::BindingType::UniformBuffer { dynamic: false },
             },
+            wgpu::BindGroupLayoutBinding {
+                binding: 3,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::StorageBuffer {
+                    dynamic: false,
+                    readonly: true,
+                },
+            },
+            wgpu::BindGroupLayoutBinding {
+                binding: 4,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::SampledTexture {
+                    multisampled: false,
+                    dimension: wgpu::TextureViewDimension::D2,
+                },
+            },
+            wgpu::BindGroupLayoutBinding {
+                binding: 5,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::Sampler,
+            },
         ],
     });
 
@@ -96,56 +265,116 @@ pub unsafe fn run_wgpu(name: &'static str, gpu_data: GpuData) -> Result<(), Box<
         bind_group_layouts: &[&bind_group_layout],
     });
 
-    let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-        layout: &pipeline_layout,
-        vertex_stage: wgpu::ProgrammableStageDescriptor {
-            module: &vs_module,
-            entry_point: "main",
-        },
-        fragment_stage: Some(wgpu::ProgrammableStageDescriptor {
-            module: &fs_module,
-            entry_point: "main",
-        }),
-        rasterization_state: Some(wgpu::RasterizationStateDescriptor {
-            front_face: wgpu::FrontFace::Ccw,
-            cull_mode: wgpu::CullMode::None,
-            depth_bias: 0,
-            depth_bias_slope_scale: 0.0,
-            depth_bias_clamp: 0.0,
-        }),
-        primitive_topology: wgpu::PrimitiveTopology::TriangleList,
-        color_states: &[wgpu::ColorStateDescriptor {
-            format: wgpu::TextureFormat::Bgra8UnormSrgb,
-            color_blend: wgpu::BlendDescriptor {
-                src_factor: wgpu::BlendFactor::SrcAlpha,
-                dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
-                operation: wgpu::BlendOperation::Add,
+    let create_raster_pipeline = |format, blend_mode| {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            layout: &pipeline_layout,
+            vertex_stage: wgpu::ProgrammableStageDescriptor {
+                module: &vs_module,
+                entry_point: "main",
             },
-            alpha_blend: wgpu::BlendDescriptor {
-                src_factor: wgpu::BlendFactor::SrcAlpha,
-                dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
-                operation: wgpu::BlendOperation::Add,
-            },
-            write_mask: wgpu::ColorWrite::ALL,
-        }],
-        depth_stencil_state: None,
-        index_format: wgpu::IndexFormat::Uint16,
-        vertex_buffers: &[
-            wgpu::VertexBufferDescriptor {
-                stride: (std::mem::size_of::<f32>() * 4) as wgpu::BufferAddress,
-                step_mode: wgpu::InputStepMode::Vertex,
-                attributes: &vertex_attr_array![0 => Float2, 1 => Float2],
-            },
-            wgpu::VertexBufferDescriptor {
-                stride: (std::mem::size_of::<u32>() * 3) as wgpu::BufferAddress,
-                step_mode: wgpu::InputStepMode::Vertex,
-                attributes: &vertex_attr_array![2 => Uint3],
+            fragment_stage: Some(wgpu::ProgrammableStageDescriptor {
+                module: &fs_module,
+                entry_point: "main",
+            }),
+            rasterization_state: Some(wgpu::RasterizationStateDescriptor {
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: wgpu::CullMode::None,
+                depth_bias: 0,
+                depth_bias_slope_scale: 0.0,
+                depth_bias_clamp: 0.0,
+            }),
+            primitive_topology: wgpu::PrimitiveTopology::TriangleList,
+            color_states: &[wgpu_color_state(format, blend_mode)],
+            depth_stencil_state: None,
+            index_format: wgpu::IndexFormat::Uint16,
+            vertex_buffers: &[
+                wgpu::VertexBufferDescriptor {
+                    stride: (std::mem::size_of::<f32>() * 4) as wgpu::BufferAddress,
+                    step_mode: wgpu::InputStepMode::Vertex,
+                    attributes: &vertex_attr_array![0 => Float2, 1 => Float2],
+                },
+                wgpu::VertexBufferDescriptor {
+                    stride: (std::mem::size_of::<u32>() * 3) as wgpu::BufferAddress,
+                    step_mode: wgpu::InputStepMode::Vertex,
+                    attributes: &vertex_attr_array![2 => Uint3],
+                },
+            ],
+            sample_count: 1,
+            sample_mask: !0,
+            alpha_to_coverage_enabled: false,
+        })
+    };
+
+    let composite_vs_module = device.create_shader_module(
+        &wgpu::read_spirv(std::io::Cursor::new(
+            &include_bytes!("../assets/composite.vs.spv")[..],
+        ))
+        .unwrap(),
+    );
+    let composite_fs_module = device.create_shader_module(
+        &wgpu::read_spirv(std::io::Cursor::new(
+            &include_bytes!("../assets/composite.fs.spv")[..],
+        ))
+        .unwrap(),
+    );
+
+    let composite_bind_group_layout =
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            bindings: &[
+                wgpu::BindGroupLayoutBinding {
+                    binding: 0,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::UniformBuffer { dynamic: false },
+                },
+                wgpu::BindGroupLayoutBinding {
+                    binding: 1,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::SampledTexture {
+                        multisampled: false,
+                        dimension: wgpu::TextureViewDimension::D2,
+                    },
+                },
+                wgpu::BindGroupLayoutBinding {
+                    binding: 2,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler,
+                },
+            ],
+        });
+
+    let composite_pipeline_layout =
+        device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            bind_group_layouts: &[&composite_bind_group_layout],
+        });
+
+    let create_composite_pipeline = |format, blend_mode| {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            layout: &composite_pipeline_layout,
+            vertex_stage: wgpu::ProgrammableStageDescriptor {
+                module: &composite_vs_module,
+                entry_point: "main",
             },
-        ],
-        sample_count: 1,
-        sample_mask: !0,
-        alpha_to_coverage_enabled: false,
-    });
+            fragment_stage: Some(wgpu::ProgrammableStageDescriptor {
+                module: &composite_fs_module,
+                entry_point: "main",
+            }),
+            rasterization_state: Some(wgpu::RasterizationStateDescriptor {
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: wgpu::CullMode::None,
+                depth_bias: 0,
+                depth_bias_slope_scale: 0.0,
+                depth_bias_clamp: 0.0,
+            }),
+            primitive_topology: wgpu::PrimitiveTopology::TriangleList,
+            color_states: &[wgpu_color_state(format, blend_mode)],
+            depth_stencil_state: None,
+            index_format: wgpu::IndexFormat::Uint16,
+            vertex_buffers: &[],
+            sample_count: 1,
+            sample_mask: !0,
+            alpha_to_coverage_enabled: false,
+        })
+    };
 
     let gpu_vertices = device.create_buffer_with_data(
         gpu_data.vertices.as_bytes(),
@@ -163,6 +392,136 @@ pub unsafe fn run_wgpu(name: &'static str, gpu_data: GpuData) -> Result<(), Box<
     let gpu_curve_ranges =
         device.create_buffer_with_data(gpu_data.curve_ranges.as_bytes(), wgpu::BufferUsage::VERTEX);
 
+    // image atlas, only accessed via texel fetches
+    let atlas_extent = wgpu::Extent3d {
+        width: atlas.width,
+        height: atlas.height,
+        depth: 1,
+    };
+    let atlas_texture = device.create_texture(&wgpu::TextureDescriptor {
+        size: atlas_extent,
+        array_layer_count: 1,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba8UnormSrgb,
+        usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
+    });
+    let atlas_view = atlas_texture.create_default_view();
+    let atlas_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+        address_mode_u: wgpu::AddressMode::ClampToEdge,
+        address_mode_v: wgpu::AddressMode::ClampToEdge,
+        address_mode_w: wgpu::AddressMode::ClampToEdge,
+        mag_filter: wgpu::FilterMode::Nearest,
+        min_filter: wgpu::FilterMode::Nearest,
+        mipmap_filter: wgpu::FilterMode::Nearest,
+        lod_min_clamp: 0.0,
+        lod_max_clamp: 0.0,
+        compare_function: wgpu::CompareFunction::Always,
+    });
+
+    // rows padded to the copy alignment
+    let row_size = 4 * atlas.width as usize;
+    let row_pitch =
+        (row_size + ROW_PITCH_ALIGNMENT - 1) / ROW_PITCH_ALIGNMENT * ROW_PITCH_ALIGNMENT;
+    let mut atlas_staging = vec![0u8; row_pitch * atlas.height as usize];
+    for (src, dst) in atlas
+        .data
+        .chunks(row_size)
+        .zip(atlas_staging.chunks_mut(row_pitch))
+    {
+        dst[..row_size].copy_from_slice(src);
+    }
+
+    let atlas_upload = device.create_buffer_with_data(&atlas_staging, wgpu::BufferUsage::COPY_SRC);
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { todo: 0 });
+    encoder.copy_buffer_to_texture(
+        wgpu::BufferCopyView {
+            buffer: &atlas_upload,
+            offset: 0,
+            row_pitch: row_pitch as u32,
+            image_height: atlas.height,
+        },
+        wgpu::TextureCopyView {
+            texture: &atlas_texture,
+            mip_level: 0,
+            array_layer: 0,
+            origin: wgpu::Origin3d::ZERO,
+        },
+        atlas_extent,
+    );
+    queue.submit(&[encoder.finish()]);
+
+    let atlas_rects = atlas.gpu_rects();
+    let gpu_atlas_rects =
+        device.create_buffer_with_data(atlas_rects.as_bytes(), wgpu::BufferUsage::STORAGE_READ);
+
+    // raster pipelines per target format and blend mode, one composite
+    // pipeline per `PopLayer` and the number of layer targets, the scene is static
+    let commands = gpu_data.balanced_commands();
+    let mut raster_pipelines = HashMap::new();
+    let mut composites = Vec::new();
+    let mut num_layers = 0;
+    let mut depth = 0;
+    let mut layer_stack = Vec::new();
+    for command in &commands {
+        match *command {
+            DrawCommand::Draw { blend_mode, .. } => {
+                let format = if depth == 0 {
+                    SWAP_CHAIN_FORMAT
+                } else {
+                    LAYER_FORMAT
+                };
+                raster_pipelines
+                    .entry((format, blend_mode))
+                    .or_insert_with(|| create_raster_pipeline(format, blend_mode));
+            }
+            DrawCommand::PushLayer {
+                opacity,
+                blend_mode,
+            } => {
+                depth += 1;
+                num_layers = num_layers.max(depth);
+                layer_stack.push((opacity, blend_mode));
+            }
+            DrawCommand::PopLayer => {
+                let (opacity, blend_mode) = layer_stack.pop().unwrap();
+                depth -= 1;
+
+                let composite_locals = CompositeLocals {
+                    opacity,
+                    over_white: if blend_mode.over_white() { 1.0 } else { 0.0 },
+                    _pad: [0; 2],
+                };
+                let composite_locals = device.create_buffer_with_data(
+                    composite_locals.as_bytes(),
+                    wgpu::BufferUsage::UNIFORM,
+                );
+                let format = if depth == 0 {
+                    SWAP_CHAIN_FORMAT
+                } else {
+                    LAYER_FORMAT
+                };
+                composites.push((
+                    create_composite_pipeline(format, blend_mode),
+                    composite_locals,
+                    depth,
+                ));
+            }
+        }
+    }
+
+    // `composite.fs` fetches layer texels at the fragment position, layer targets
+    // have to match the swap chain and are recreated on resize
+    let mut layer_targets = create_layer_targets(&device, size.width, size.height, num_layers);
+    let mut composite_bind_groups = create_composite_bind_groups(
+        &device,
+        &composite_bind_group_layout,
+        &composites,
+        &layer_targets,
+        &atlas_sampler,
+    );
+
     let mut viewport = Viewport {
         position: (0.0, 0.0),
         scaling_y: size.height as _,
@@ -204,6 +563,21 @@ pub unsafe fn run_wgpu(name: &'static str, gpu_data: GpuData) -> Result<(), Box<
                     range: 0..32,
                 },
             },
+            wgpu::Binding {
+                binding: 3,
+                resource: wgpu::BindingResource::Buffer {
+                    buffer: &gpu_atlas_rects,
+                    range: 0..atlas_rects.as_bytes().len() as _,
+                },
+            },
+            wgpu::Binding {
+                binding: 4,
+                resource: wgpu::BindingResource::TextureView(&atlas_view),
+            },
+            wgpu::Binding {
+                binding: 5,
+                resource: wgpu::BindingResource::Sampler(&atlas_sampler),
+            },
         ],
     });
 
@@ -228,6 +602,16 @@ pub unsafe fn run_wgpu(name: &'static str, gpu_data: GpuData) -> Result<(), Box<
                     sc_desc.width = size.width;
                     sc_desc.height = size.height;
                     swap_chain = device.create_swap_chain(&surface, &sc_desc);
+
+                    layer_targets =
+                        create_layer_targets(&device, size.width, size.height, num_layers);
+                    composite_bind_groups = create_composite_bind_groups(
+                        &device,
+                        &composite_bind_group_layout,
+                        &composites,
+                        &layer_targets,
+                        &atlas_sampler,
+                    );
                 }
                 _ => (),
             },
@@ -283,35 +667,74 @@ pub unsafe fn run_wgpu(name: &'static str, gpu_data: GpuData) -> Result<(), Box<
 
                 let mut encoder =
                     device.create_command_encoder(&wgpu::CommandEncoderDescriptor { todo: 0 });
-                {
-                    encoder.copy_buffer_to_buffer(
-                        &temp_buf,
-                        0,
-                        &locals,
-                        0,
-                        std::mem::size_of::<Locals>() as _,
-                    );
-                    let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                        color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
-                            attachment: &frame.view,
-                            resolve_target: None,
-                            load_op: wgpu::LoadOp::Clear,
-                            store_op: wgpu::StoreOp::Store,
-                            clear_color: wgpu::Color {
-                                r: 1.0,
-                                g: 1.0,
-                                b: 1.0,
-                                a: 1.0,
-                            },
-                        }],
-                        depth_stencil_attachment: None,
-                    });
-                    rpass.set_pipeline(&render_pipeline);
-                    rpass.set_bind_group(0, &bind_group, &[]);
-                    rpass.set_vertex_buffers(0, &[(&gpu_bbox, 0), (&gpu_curve_ranges, 0)]);
-
-                    let num_vertices = gpu_data.bbox.len() as u32 / 4;
-                    rpass.draw(0..num_vertices, 0..1);
+                encoder.copy_buffer_to_buffer(
+                    &temp_buf,
+                    0,
+                    &locals,
+                    0,
+                    std::mem::size_of::<Locals>() as _,
+                );
+                begin_pass(
+                    &mut encoder,
+                    &frame.view,
+                    wgpu::LoadOp::Clear,
+                    wgpu::Color {
+                        r: 1.0,
+                        g: 1.0,
+                        b: 1.0,
+                        a: 1.0,
+                    },
+                );
+
+                // one render pass per draw and composite, pushed layers are cleared
+                // by an otherwise empty pass
+                let mut depth = 0;
+                let mut composite = composites.iter().zip(&composite_bind_groups);
+                for command in &commands {
+                    match *command {
+                        DrawCommand::Draw {
+                            start,
+                            end,
+                            blend_mode,
+                        } => {
+                            let (target, format) = if depth == 0 {
+                                (&frame.view, SWAP_CHAIN_FORMAT)
+                            } else {
+                                (&layer_targets[depth - 1].1, LAYER_FORMAT)
+                            };
+                            let pipeline = &raster_pipelines[&(format, blend_mode)];
+                            let mut rpass =
+                                begin_pass(&mut encoder, target, wgpu::LoadOp::Load, TRANSPARENT);
+                            rpass.set_pipeline(pipeline);
+                            rpass.set_bind_group(0, &bind_group, &[]);
+                            rpass.set_vertex_buffers(0, &[(&gpu_bbox, 0), (&gpu_curve_ranges, 0)]);
+                            rpass.draw(start..end, 0..1);
+                        }
+                        DrawCommand::PushLayer { .. } => {
+                            depth += 1;
+                            begin_pass(
+                                &mut encoder,
+                                &layer_targets[depth - 1].1,
+                                wgpu::LoadOp::Clear,
+                                TRANSPARENT,
+                            );
+                        }
+                        DrawCommand::PopLayer => {
+                            depth -= 1;
+                            let target = if depth == 0 {
+                                &frame.view
+                            } else {
+                                &layer_targets[depth - 1].1
+                            };
+                            let ((pipeline, _, _), composite_bind_group) =
+                                composite.next().unwrap();
+                            let mut rpass =
+                                begin_pass(&mut encoder, target, wgpu::LoadOp::Load, TRANSPARENT);
+                            rpass.set_pipeline(pipeline);
+                            rpass.set_bind_group(0, composite_bind_group, &[]);
+                            rpass.draw(0..3, 0..1);
+                        }
+                    }
                 }
 
                 queue.submit(&[encoder.finish()]);
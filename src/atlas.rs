@@ -0,0 +1,148 @@
+pub type ImageId = u32;
+
+/// Alpha representation of image data passed to `ImageAtlas::add`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum AlphaMode {
+    Straight,
+    Premultiplied,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct AtlasRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Texture registry for image brushes.
+///
+/// Images are packed into a single RGBA8 texture with a simple shelf allocator.
+/// `data` holds straight alpha, as required for sRGB decoding; the shader premultiplies texels.
+/// Renderers upload `data` once and bind the rect table (`gpu_rects`) next to the scene buffers.
+pub struct ImageAtlas {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+    rects: Vec<AtlasRect>,
+    shelf_x: u32,
+    shelf_y: u32,
+    shelf_height: u32,
+}
+
+impl ImageAtlas {
+    pub fn new(width: u32, height: u32) -> Self {
+        let size = (width as usize)
+            .checked_mul(height as usize)
+            .and_then(|texels| texels.checked_mul(4))
+            .expect("atlas size overflows usize");
+        ImageAtlas {
+            width,
+            height,
+            data: vec![0; size],
+            rects: Vec::new(),
+            shelf_x: 0,
+            shelf_y: 0,
+            shelf_height: 0,
+        }
+    }
+
+    /// Add an image with tightly packed RGBA8 rows.
+    ///
+    /// Premultiplied images are converted to straight alpha, fully transparent texels become black.
+    /// Returns `None` if the length of `data` doesn't match the size or the image doesn't fit
+    /// into the remaining space, the atlas is left unchanged in this case. Empty images don't
+    /// occupy any space.
+    pub fn add(
+        &mut self,
+        width: u32,
+        height: u32,
+        data: &[u8],
+        alpha_mode: AlphaMode,
+    ) -> Option<ImageId> {
+        if data.len() as u64 != 4 * width as u64 * height as u64 {
+            return None;
+        }
+
+        // empty images occupy no space and are transparent
+        if width == 0 || height == 0 {
+            let id = self.rects.len() as ImageId;
+            self.rects.push(AtlasRect {
+                x: 0,
+                y: 0,
+                width,
+                height,
+            });
+            return Some(id);
+        }
+
+        // u64 to avoid overflows for large images
+        let (mut x, mut y, mut shelf_height) = (
+            self.shelf_x as u64,
+            self.shelf_y as u64,
+            self.shelf_height as u64,
+        );
+        if x + width as u64 > self.width as u64 {
+            // start a new shelf
+            x = 0;
+            y += shelf_height;
+            shelf_height = 0;
+        }
+
+        if x + width as u64 > self.width as u64 || y + height as u64 > self.height as u64 {
+            return None;
+        }
+
+        let rect = AtlasRect {
+            x: x as u32,
+            y: y as u32,
+            width,
+            height,
+        };
+
+        let row_size = 4 * width as usize;
+        for row in 0..height {
+            let src = row as usize * row_size;
+            let dst = 4 * ((rect.y + row) as usize * self.width as usize + rect.x as usize);
+            let row_data = &mut self.data[dst..dst + row_size];
+            row_data.copy_from_slice(&data[src..src + row_size]);
+
+            if alpha_mode == AlphaMode::Premultiplied {
+                for texel in row_data.chunks_mut(4) {
+                    let a = texel[3] as u32;
+                    for c in &mut texel[..3] {
+                        *c = if a == 0 {
+                            0
+                        } else {
+                            ((*c as u32 * 255 + a / 2) / a).min(255) as u8
+                        };
+                    }
+                }
+            }
+        }
+
+        self.shelf_x = rect.x + width;
+        self.shelf_y = rect.y;
+        self.shelf_height = shelf_height.max(height as u64) as u32;
+
+        let id = self.rects.len() as ImageId;
+        self.rects.push(rect);
+        Some(id)
+    }
+
+    pub fn rect(&self, id: ImageId) -> Option<AtlasRect> {
+        self.rects.get(id as usize).cloned()
+    }
+
+    /// Rect table as `[x, y, width, height]` per image, never empty.
+    pub fn gpu_rects(&self) -> Vec<u32> {
+        if self.rects.is_empty() {
+            return vec![0; 4];
+        }
+
+        self.rects
+            .iter()
+            .flat_map(|rect| vec![rect.x, rect.y, rect.width, rect.height])
+            .collect()
+    }
+}
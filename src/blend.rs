@@ -0,0 +1,116 @@
+/// Compositing operator of a draw or layer with its backdrop.
+///
+/// Porter-Duff operators and `Screen` are exact. `Multiply` maps to fixed-function blending
+/// and is exact for opaque backdrops only, `Darken` and `Lighten` additionally require an
+/// opaque source and approximate antialiased edges.
+/// Modes which don't preserve the backdrop outside of the source (e.g. `Src`, `SrcIn`)
+/// affect the whole rect of the draw.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum BlendMode {
+    Clear,
+    Src,
+    Dst,
+    SrcOver,
+    DstOver,
+    SrcIn,
+    DstIn,
+    SrcOut,
+    DstOut,
+    SrcAtop,
+    DstAtop,
+    Xor,
+    /// Additive blending.
+    Plus,
+    Multiply,
+    Screen,
+    Darken,
+    Lighten,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) enum BlendFactor {
+    Zero,
+    One,
+    OneMinusSrcColor,
+    SrcAlpha,
+    OneMinusSrcAlpha,
+    DstColor,
+    DstAlpha,
+    OneMinusDstAlpha,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) enum BlendOp {
+    Add,
+    Min,
+    Max,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) struct BlendChannel {
+    pub src: BlendFactor,
+    pub dst: BlendFactor,
+    pub op: BlendOp,
+}
+
+// Fixed-function blend state, source and destination are premultiplied.
+pub(crate) struct BlendState {
+    pub color: BlendChannel,
+    pub alpha: BlendChannel,
+}
+
+impl BlendMode {
+    pub(crate) fn state(&self) -> BlendState {
+        use self::BlendFactor::*;
+
+        let channel = |src, dst| BlendChannel {
+            src,
+            dst,
+            op: BlendOp::Add,
+        };
+        let src_over = channel(One, OneMinusSrcAlpha);
+
+        let porter_duff = |src, dst| BlendState {
+            color: channel(src, dst),
+            alpha: channel(src, dst),
+        };
+        let separable = |color| BlendState {
+            color,
+            alpha: src_over,
+        };
+
+        match *self {
+            BlendMode::Clear => porter_duff(Zero, Zero),
+            BlendMode::Src => porter_duff(One, Zero),
+            BlendMode::Dst => porter_duff(Zero, One),
+            BlendMode::SrcOver => porter_duff(One, OneMinusSrcAlpha),
+            BlendMode::DstOver => porter_duff(OneMinusDstAlpha, One),
+            BlendMode::SrcIn => porter_duff(DstAlpha, Zero),
+            BlendMode::DstIn => porter_duff(Zero, SrcAlpha),
+            BlendMode::SrcOut => porter_duff(OneMinusDstAlpha, Zero),
+            BlendMode::DstOut => porter_duff(Zero, OneMinusSrcAlpha),
+            BlendMode::SrcAtop => porter_duff(DstAlpha, OneMinusSrcAlpha),
+            BlendMode::DstAtop => porter_duff(OneMinusDstAlpha, SrcAlpha),
+            BlendMode::Xor => porter_duff(OneMinusDstAlpha, OneMinusSrcAlpha),
+            BlendMode::Plus => porter_duff(One, One),
+            BlendMode::Multiply => separable(channel(DstColor, OneMinusSrcAlpha)),
+            BlendMode::Screen => separable(channel(One, OneMinusSrcColor)),
+            BlendMode::Darken => separable(BlendChannel {
+                src: One,
+                dst: One,
+                op: BlendOp::Min,
+            }),
+            BlendMode::Lighten => separable(BlendChannel {
+                src: One,
+                dst: One,
+                op: BlendOp::Max,
+            }),
+        }
+    }
+
+    // Sources are composited over white before blending with `BlendOp::Min`, which
+    // would darken the backdrop with transparent black otherwise.
+    pub(crate) fn over_white(&self) -> bool {
+        self.state().color.op == BlendOp::Min
+    }
+}
@@ -1,16 +1,73 @@
-use crate::glm;
+use crate::{glm, ImageId, Transform};
 
+/// RGBA8 color with straight (non-premultiplied) alpha.
+///
+/// Colors are converted to premultiplied alpha when packed into `GpuData`.
 pub type Color = [u8; 4];
 
+/// Color stop of a gradient brush.
+///
+/// Gradients without stops or with more than 65535 stops paint nothing.
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub struct GradientStop {
-    pub position: glm::Vec2,
+    /// Position along the gradient in `[0, 1]`.
+    pub offset: f32,
     pub color: Color,
 }
 
+/// Behavior of gradients outside of `[0, 1]`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SpreadMode {
+    Pad,
+    Repeat,
+    Reflect,
+}
+
+/// Texel filter for image brushes.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Sampling {
+    Nearest,
+    Linear,
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum Brush {
     Color(Color),
     LinearGradient {
-        stop0: GradientStop,
-        stop1: GradientStop,
+        start: glm::Vec2,
+        end: glm::Vec2,
+        stops: Vec<GradientStop>,
+        spread: SpreadMode,
+    },
+    /// Gradient from the `focal` point (offset 0) to the circle around `center` (offset 1).
+    /// The focal point is moved inside the circle if required, a circle with zero radius
+    /// is painted with the last stop.
+    RadialGradient {
+        center: glm::Vec2,
+        radius: f32,
+        focal: glm::Vec2,
+        stops: Vec<GradientStop>,
+        spread: SpreadMode,
+    },
+    /// Angular gradient around `center`, counter-clockwise from `start_angle` (offset 0)
+    /// to `end_angle` (offset 1). Angles in radians.
+    ///
+    /// `end_angle` is wrapped by full turns into `(start_angle, start_angle + 2pi]`,
+    /// equal angles paint the last stop.
+    SweepGradient {
+        center: glm::Vec2,
+        start_angle: f32,
+        end_angle: f32,
+        stops: Vec<GradientStop>,
+    },
+    /// Image from the renderer's `ImageAtlas`. `transform` maps image texel coordinates
+    /// to world space, `extend` defines the behavior outside of the image.
+    ///
+    /// Unknown or empty images are transparent, draws with a singular `transform` are skipped.
+    Image {
+        image_id: ImageId,
+        transform: Transform,
+        sampling: Sampling,
+        extend: SpreadMode,
     },
 }
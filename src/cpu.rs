@@ -0,0 +1,916 @@
+use crate::gpu::{
+    FILL_OVER_WHITE, FILL_RULE_EVEN_ODD, PRIMITIVE_ARC, PRIMITIVE_CIRCLE, PRIMITIVE_CLIP,
+    PRIMITIVE_FILL_COLOR, PRIMITIVE_FILL_IMAGE, PRIMITIVE_FILL_LINEAR_GRADIENT,
+    PRIMITIVE_FILL_RADIAL_GRADIENT, PRIMITIVE_FILL_SWEEP_GRADIENT, PRIMITIVE_LINE, PRIMITIVE_MASK,
+    PRIMITIVE_QUADRATIC, PRIMITIVE_RECT, PRIMITIVE_ROUNDED_RECT, PRIMITIVE_SHADOW_RECT,
+    SAMPLING_NEAREST, SPREAD_PAD, SPREAD_REFLECT, SPREAD_REPEAT,
+};
+use crate::{
+    glm, BlendChannel, BlendFactor, BlendMode, BlendOp, DrawCommand, GpuData, ImageAtlas, Viewport,
+};
+use image::RgbaImage;
+use std::f32::consts::PI;
+
+/// Render `gpu_data` on the CPU, mirroring `lanka.vs`/`lanka.fs` and the blend state of the backends.
+///
+/// Image brushes sample an empty atlas, see `render_with_atlas`.
+pub fn render(gpu_data: &GpuData, viewport: &Viewport, width: u32, height: u32) -> RgbaImage {
+    render_with_atlas(gpu_data, &ImageAtlas::new(1, 1), viewport, width, height)
+}
+
+/// Render `gpu_data` on the CPU with the images of `atlas`.
+///
+/// Blending happens in linear space like on an sRGB target, starting from a transparent
+/// background. The result is sRGB encoded with straight alpha.
+pub fn render_with_atlas(
+    gpu_data: &GpuData,
+    atlas: &ImageAtlas,
+    viewport: &Viewport,
+    width: u32,
+    height: u32,
+) -> RgbaImage {
+    let scene = Scene {
+        vertices: &gpu_data.vertices,
+        primitives: &gpu_data.primitives,
+        atlas,
+        atlas_rects: atlas.gpu_rects(),
+    };
+    let rasterizer = Rasterizer {
+        viewport: viewport.get_rect(),
+        width,
+        height,
+    };
+
+    let num_pixels = (width * height) as usize;
+    let mut targets = vec![vec![glm::vec4(0.0, 0.0, 0.0, 0.0); num_pixels]];
+    let mut layers = Vec::new();
+    for command in gpu_data.balanced_commands() {
+        match command {
+            DrawCommand::Draw {
+                start,
+                end,
+                blend_mode,
+            } => {
+                let target = targets.last_mut().unwrap();
+                for triangle in (start..end).step_by(3) {
+                    if triangle + 3 > end {
+                        break;
+                    }
+                    let vertices = [
+                        bbox_vertex(gpu_data, triangle),
+                        bbox_vertex(gpu_data, triangle + 1),
+                        bbox_vertex(gpu_data, triangle + 2),
+                    ];
+                    rasterizer.draw_triangle(&vertices, |x, y, fragment| {
+                        let color = scene.shade(&fragment);
+                        let pixel = &mut target[(y * width + x) as usize];
+                        *pixel = blend(blend_mode, color, *pixel);
+                    });
+                }
+            }
+            DrawCommand::PushLayer {
+                opacity,
+                blend_mode,
+            } => {
+                targets.push(vec![glm::vec4(0.0, 0.0, 0.0, 0.0); num_pixels]);
+                layers.push((opacity, blend_mode));
+            }
+            DrawCommand::PopLayer => {
+                let (opacity, blend_mode) = layers.pop().unwrap();
+                let layer = targets.pop().unwrap();
+                let parent = targets.last_mut().unwrap();
+                for (dst, src) in parent.iter_mut().zip(layer) {
+                    // see `composite.fs`
+                    let mut src = src * opacity;
+                    if blend_mode.over_white() {
+                        src = over_white(src);
+                    }
+                    *dst = blend(blend_mode, src, *dst);
+                }
+            }
+        }
+    }
+
+    let mut image = RgbaImage::new(width, height);
+    for (pixel, color) in image.pixels_mut().zip(&targets[0]) {
+        pixel.0 = encode_pixel(*color);
+    }
+    image
+}
+
+// Linear premultiplied color -> sRGB encoded with straight alpha.
+pub(crate) fn encode_pixel(color: glm::Vec4) -> [u8; 4] {
+    let a = color.w;
+    let straight = |c: f32| {
+        if a > 0.0 {
+            linear_to_srgb(c / a)
+        } else {
+            0.0
+        }
+    };
+    [
+        unorm8(straight(color.x)),
+        unorm8(straight(color.y)),
+        unorm8(straight(color.z)),
+        unorm8(a),
+    ]
+}
+
+struct BboxVertex {
+    pos_world: glm::Vec2,
+    pos_curve: glm::Vec2,
+    curve_range: [u32; 3],
+}
+
+fn bbox_vertex(gpu_data: &GpuData, i: u32) -> BboxVertex {
+    let b = 4 * i as usize;
+    let c = 3 * i as usize;
+    BboxVertex {
+        pos_world: glm::vec2(gpu_data.bbox[b], gpu_data.bbox[b + 1]),
+        pos_curve: glm::vec2(gpu_data.bbox[b + 2], gpu_data.bbox[b + 3]),
+        curve_range: [
+            gpu_data.curve_ranges[c],
+            gpu_data.curve_ranges[c + 1],
+            gpu_data.curve_ranges[c + 2],
+        ],
+    }
+}
+
+// Interpolated fragment shader inputs.
+struct Fragment {
+    pos_world: glm::Vec2,
+    pos_curve: glm::Vec2,
+    // `fwidth(pos_curve)`, constant per triangle
+    dxdy: glm::Vec2,
+    curve_range: [u32; 3],
+}
+
+struct Rasterizer {
+    viewport: [f32; 4],
+    width: u32,
+    height: u32,
+}
+
+impl Rasterizer {
+    // world -> pixel coordinates, y pointing down
+    fn to_screen(&self, p: glm::Vec2) -> glm::Vec2 {
+        let [cx, cy, w, h] = self.viewport;
+        glm::vec2(
+            (p.x - cx + 0.5 * w) / w * self.width as f32,
+            (cy + 0.5 * h - p.y) / h * self.height as f32,
+        )
+    }
+
+    // Calls `shade` for all pixel centers covered by the triangle, shared edges
+    // are resolved with the top-left rule.
+    fn draw_triangle<F>(&self, vertices: &[BboxVertex; 3], mut shade: F)
+    where
+        F: FnMut(u32, u32, Fragment),
+    {
+        let mut v = [0, 1, 2];
+        let mut s = [
+            self.to_screen(vertices[0].pos_world),
+            self.to_screen(vertices[1].pos_world),
+            self.to_screen(vertices[2].pos_world),
+        ];
+
+        let edge = |a: glm::Vec2, b: glm::Vec2, p: glm::Vec2| {
+            (b.x - a.x) * (p.y - a.y) - (b.y - a.y) * (p.x - a.x)
+        };
+
+        let mut area = edge(s[0], s[1], s[2]);
+        if area == 0.0 || !area.is_finite() {
+            return;
+        }
+        if area < 0.0 {
+            v.swap(1, 2);
+            s.swap(1, 2);
+            area = -area;
+        }
+
+        let top_left = |a: glm::Vec2, b: glm::Vec2| (a.y == b.y && b.x > a.x) || b.y < a.y;
+        let edges = [(s[1], s[2]), (s[2], s[0]), (s[0], s[1])];
+        let bias = [
+            top_left(edges[0].0, edges[0].1),
+            top_left(edges[1].0, edges[1].1),
+            top_left(edges[2].0, edges[2].1),
+        ];
+
+        // barycentric gradients in pixel space
+        let dldx = [
+            -(s[2].y - s[1].y) / area,
+            -(s[0].y - s[2].y) / area,
+            -(s[1].y - s[0].y) / area,
+        ];
+        let dldy = [
+            (s[2].x - s[1].x) / area,
+            (s[0].x - s[2].x) / area,
+            (s[1].x - s[0].x) / area,
+        ];
+        let curve = [
+            vertices[v[0]].pos_curve,
+            vertices[v[1]].pos_curve,
+            vertices[v[2]].pos_curve,
+        ];
+        let world = [
+            vertices[v[0]].pos_world,
+            vertices[v[1]].pos_world,
+            vertices[v[2]].pos_world,
+        ];
+        let dcdx = curve[0] * dldx[0] + curve[1] * dldx[1] + curve[2] * dldx[2];
+        let dcdy = curve[0] * dldy[0] + curve[1] * dldy[1] + curve[2] * dldy[2];
+        let dxdy = glm::vec2(dcdx.x.abs() + dcdy.x.abs(), dcdx.y.abs() + dcdy.y.abs());
+        // flat inputs use the provoking vertex
+        let curve_range = vertices[2].curve_range;
+
+        let min_x = s[0].x.min(s[1].x).min(s[2].x).floor().max(0.0) as u32;
+        let min_y = s[0].y.min(s[1].y).min(s[2].y).floor().max(0.0) as u32;
+        let max_x = (s[0].x.max(s[1].x).max(s[2].x).ceil().max(0.0) as u32).min(self.width);
+        let max_y = (s[0].y.max(s[1].y).max(s[2].y).ceil().max(0.0) as u32).min(self.height);
+
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                let p = glm::vec2(x as f32 + 0.5, y as f32 + 0.5);
+                let w = [
+                    edge(edges[0].0, edges[0].1, p),
+                    edge(edges[1].0, edges[1].1, p),
+                    edge(edges[2].0, edges[2].1, p),
+                ];
+                let inside = w
+                    .iter()
+                    .zip(&bias)
+                    .all(|(&w, &bias)| w > 0.0 || (w == 0.0 && bias));
+                if !inside {
+                    continue;
+                }
+                let l = [w[0] / area, w[1] / area, w[2] / area];
+
+                shade(
+                    x,
+                    y,
+                    Fragment {
+                        pos_world: world[0] * l[0] + world[1] * l[1] + world[2] * l[2],
+                        pos_curve: curve[0] * l[0] + curve[1] * l[1] + curve[2] * l[2],
+                        dxdy,
+                        curve_range,
+                    },
+                );
+            }
+        }
+    }
+}
+
+struct Scene<'a> {
+    vertices: &'a [u32],
+    primitives: &'a [u32],
+    atlas: &'a ImageAtlas,
+    atlas_rects: Vec<u32>,
+}
+
+impl<'a> Scene<'a> {
+    fn half2(&self, i: usize) -> glm::Vec2 {
+        let v = self.vertices[i];
+        glm::vec2(
+            half::f16::from_bits(v as u16).to_f32(),
+            half::f16::from_bits((v >> 16) as u16).to_f32(),
+        )
+    }
+
+    fn float(&self, i: usize) -> f32 {
+        f32::from_bits(self.vertices[i])
+    }
+
+    fn unorm4(&self, i: usize) -> glm::Vec4 {
+        let v = self.vertices[i];
+        let c = |shift: u32| ((v >> shift) & 0xFF) as f32 / 255.0;
+        glm::vec4(c(0), c(8), c(16), c(24))
+    }
+
+    // see `gradient_eval` in `lanka.fs`
+    fn gradient_eval(&self, base_vertex: &mut usize, t: f32) -> glm::Vec4 {
+        let header = self.vertices[*base_vertex];
+        *base_vertex += 1;
+        let num_stops = (header & 0xFFFF) as usize;
+        let spread = header >> 16;
+
+        let t = match spread {
+            SPREAD_PAD => clamp(t, 0.0, 1.0),
+            SPREAD_REPEAT => fract(t),
+            SPREAD_REFLECT => 1.0 - (glsl_mod(t, 2.0) - 1.0).abs(),
+            _ => t,
+        };
+
+        let mut prev_offset = self.float(*base_vertex);
+        let mut prev_color = self.unorm4(*base_vertex + 1);
+        let mut color = prev_color;
+        for s in 1..num_stops {
+            let offset = self.float(*base_vertex + 2 * s);
+            let c = self.unorm4(*base_vertex + 2 * s + 1);
+            if t > prev_offset {
+                let f = clamp(
+                    (t - prev_offset) / (offset - prev_offset).max(1e-6),
+                    0.0,
+                    1.0,
+                );
+                color = mix4(prev_color, c, f);
+            }
+            prev_offset = offset;
+            prev_color = c;
+        }
+
+        *base_vertex += 2 * num_stops;
+        color
+    }
+
+    // premultiplied linear texel, see `image_texel` in `lanka.fs`
+    fn image_texel(&self, rect: [u32; 4], x: i32, y: i32, extend: u32) -> glm::Vec4 {
+        let (w, h) = (rect[2] as i32, rect[3] as i32);
+        let qx = rect[0] as i32 + image_extend(x, w, extend);
+        let qy = rect[1] as i32 + image_extend(y, h, extend);
+        let i = 4 * (qy as usize * self.atlas.width as usize + qx as usize);
+        let texel = &self.atlas.data[i..i + 4];
+        let a = texel[3] as f32 / 255.0;
+        glm::vec4(
+            srgb_to_linear(texel[0] as f32 / 255.0) * a,
+            srgb_to_linear(texel[1] as f32 / 255.0) * a,
+            srgb_to_linear(texel[2] as f32 / 255.0) * a,
+            a,
+        )
+    }
+
+    // port of the `lanka.fs` main function, returns the premultiplied color
+    fn shade(&self, fragment: &Fragment) -> glm::Vec4 {
+        let tile_center = fragment.pos_curve;
+        let pos_world = fragment.pos_world;
+        let dxdy = fragment.dxdy;
+        let unit = glm::vec2(1.0 / dxdy.x, 1.0 / dxdy.y);
+        let half_x = 0.5 * dxdy.x;
+        let half_y = 0.5 * dxdy.y;
+
+        let mut color = glm::vec4(1.0, 0.0, 0.0, 1.0);
+        let mut coverage = 0.0;
+        let mut clip = 1.0;
+
+        let [vertex_start, prim_start, prim_end] = fragment.curve_range;
+        let mut base_vertex = vertex_start as usize;
+        for &primitive in &self.primitives[prim_start as usize..prim_end as usize] {
+            match primitive & PRIMITIVE_MASK {
+                PRIMITIVE_LINE => {
+                    let p0 = self.half2(base_vertex) - tile_center;
+                    let p1 = self.half2(base_vertex + 1) - tile_center;
+                    base_vertex += 2;
+
+                    if p0.y.max(p1.y) < -half_y {
+                        continue;
+                    }
+
+                    let xx0 = clamp(p0.x, -half_x, half_x);
+                    let xx1 = clamp(p1.x, -half_x, half_x);
+                    let xx = (xx1 - xx0) * unit.x;
+
+                    let mut cy = 1.0;
+                    if xx != 0.0 && p0.y.min(p1.y) < half_y {
+                        let t = line_raycast(p0.x, p1.x, 0.5 * (xx0 + xx1));
+                        let d = mix(p0.y, p1.y, t) * unit.y;
+                        let tangent = glm::vec2((p1.x - p0.x).abs(), (p1.y - p0.y).abs());
+                        let m = tangent.x / tangent.x.max(tangent.y);
+                        cy = cdf(d, m);
+                    }
+
+                    coverage += cy * xx;
+                }
+                PRIMITIVE_QUADRATIC => {
+                    let p0 = self.half2(base_vertex) - tile_center;
+                    let p1 = self.half2(base_vertex + 1) - tile_center;
+                    let p2 = self.half2(base_vertex + 2) - tile_center;
+                    base_vertex += 3;
+
+                    if p0.y.max(p2.y) < -half_y {
+                        continue;
+                    }
+
+                    let xx0 = clamp(p0.x, -half_x, half_x);
+                    let xx1 = clamp(p2.x, -half_x, half_x);
+                    let xx = (xx1 - xx0) * unit.x;
+
+                    let mut cy = 1.0;
+                    if xx != 0.0 && p0.y.min(p2.y) < half_y {
+                        let t = quad_raycast(p0.x, p1.x, p2.x, 0.5 * (xx0 + xx1));
+                        let d = quad_eval(p0.y, p1.y, p2.y, t) * unit.y;
+                        let tangent = (p1 - p0) * (1.0 - t) + (p2 - p1) * t;
+                        let tangent = glm::vec2(tangent.x.abs(), tangent.y.abs());
+                        let m = tangent.x / tangent.x.max(tangent.y);
+                        cy = cdf(d, m);
+                    }
+
+                    coverage += cy * xx;
+                }
+                PRIMITIVE_CIRCLE => {
+                    let center = self.half2(base_vertex) - tile_center;
+                    let radius = self.float(base_vertex + 1);
+                    base_vertex += 2;
+
+                    let xx0 = clamp(center.x - radius, -half_x, half_x);
+                    let xx1 = clamp(center.x + radius, -half_x, half_x);
+                    let xx = (xx1 - xx0) * unit.x;
+
+                    if xx == 0.0 {
+                        continue;
+                    }
+
+                    if center.y + radius > -half_y && center.y - radius < half_y {
+                        let dx = 0.5 * (xx0 + xx1) - center.x;
+                        let dy = (radius * radius - dx * dx).sqrt();
+                        let ddy = dy.abs() / radius;
+                        let dy0 = (center.y - dy) * unit.y;
+                        let dy1 = (center.y + dy) * unit.y;
+
+                        coverage -= xx * cdf(dy0, ddy);
+                        coverage += xx * cdf(dy1, ddy);
+                    }
+                }
+                PRIMITIVE_ARC => {
+                    let center = self.half2(base_vertex) - tile_center;
+                    let d0 = self.half2(base_vertex + 1);
+                    let d1 = self.half2(base_vertex + 2);
+                    base_vertex += 3;
+                    let p0 = center + d0;
+                    let p1 = center + d1;
+
+                    let xx0 = clamp(p0.x, -half_x, half_x);
+                    let xx1 = clamp(p1.x, -half_x, half_x);
+                    let xx = (xx1 - xx0) * unit.x;
+
+                    if xx == 0.0 {
+                        continue;
+                    }
+
+                    let mut cy = 0.0;
+                    if p0.y.max(p1.y) > -half_y {
+                        if p0.y.min(p1.y) < half_y {
+                            let diry = d0.y + d1.y;
+                            let sign = (diry > center.y) as i32 - (center.y > diry) as i32;
+
+                            let dx = 0.5 * (xx0 + xx1) - center.x;
+                            let dy = (glm::dot(&d0, &d0) - dx * dx).sqrt();
+                            let d = (center.y + sign as f32 * dy) * unit.y;
+                            let ddy = dy.abs() / glm::length(&d0);
+                            cy = cdf(d, ddy);
+                        } else {
+                            cy = 1.0;
+                        }
+                    }
+
+                    coverage += cy * xx;
+                }
+                PRIMITIVE_RECT => {
+                    let p0 = self.half2(base_vertex) - tile_center;
+                    let p1 = self.half2(base_vertex + 1) - tile_center;
+                    base_vertex += 2;
+
+                    let xx0 = clamp(p0.x, -half_x, half_x);
+                    let xx1 = clamp(p1.x, -half_x, half_x);
+                    let xx = (xx1 - xx0) * unit.x;
+
+                    let dy0 = p0.y * unit.y;
+                    let dy1 = p1.y * unit.y;
+
+                    coverage -= xx * cdf(dy0, 1.0);
+                    coverage += xx * cdf(dy1, 1.0);
+                }
+                PRIMITIVE_ROUNDED_RECT => {
+                    let p0 = self.half2(base_vertex) - tile_center;
+                    let p1 = self.half2(base_vertex + 1) - tile_center;
+                    let r_lower = self.half2(base_vertex + 2);
+                    let r_upper = self.half2(base_vertex + 3);
+                    base_vertex += 4;
+
+                    let lower = glm::vec2(p0.x.min(p1.x), p0.y.min(p1.y));
+                    let upper = glm::vec2(p0.x.max(p1.x), p0.y.max(p1.y));
+                    let dir = glm::vec2(sign(p1.x - p0.x), sign(p1.y - p0.y));
+
+                    let xx0 = clamp(lower.x, -half_x, half_x);
+                    let xx1 = clamp(upper.x, -half_x, half_x);
+                    let xx = (xx1 - xx0) * unit.x;
+
+                    if xx == 0.0 {
+                        continue;
+                    }
+
+                    let x = 0.5 * (xx0 + xx1);
+                    let e0 = rounded_edge(x, lower.x, upper.x, r_lower.x, r_lower.y);
+                    let e1 = rounded_edge(x, lower.x, upper.x, r_upper.y, r_upper.x);
+
+                    let dy0 = (lower.y + e0.x) * unit.y;
+                    let dy1 = (upper.y - e1.x) * unit.y;
+
+                    coverage += dir.x * dir.y * xx * (cdf(dy1, e1.y) - cdf(dy0, e0.y));
+                }
+                PRIMITIVE_SHADOW_RECT => {
+                    let p0 = self.half2(base_vertex) - tile_center;
+                    let p1 = self.half2(base_vertex + 1) - tile_center;
+                    let sigma = self.float(base_vertex + 2);
+                    let radius = self.float(base_vertex + 3);
+                    base_vertex += 4;
+
+                    if radius > 0.0 {
+                        let lower = glm::vec2(p0.x.min(p1.x), p0.y.min(p1.y));
+                        let upper = glm::vec2(p0.x.max(p1.x), p0.y.max(p1.y));
+                        let dir = glm::vec2(sign(p1.x - p0.x), sign(p1.y - p0.y));
+                        coverage += dir.x
+                            * dir.y
+                            * rounded_shadow(
+                                (lower + upper) * 0.5,
+                                (upper - lower) * 0.5,
+                                sigma,
+                                radius,
+                            );
+                    } else {
+                        // separable, exact
+                        let norm = 0.5f32.sqrt() / sigma;
+                        let sy = 0.5 * (erf(p1.y * norm) - erf(p0.y * norm));
+                        let sx = 0.5 * (erf(p1.x * norm) - erf(p0.x * norm));
+
+                        coverage += sy * sx;
+                    }
+                }
+                PRIMITIVE_CLIP => {
+                    clip *= fill_coverage(coverage, primitive);
+                    coverage = 0.0;
+                }
+                PRIMITIVE_FILL_COLOR => {
+                    let brush = self.unorm4(base_vertex);
+                    base_vertex += 1;
+                    color = brush * (clip * fill_coverage(coverage, primitive));
+                    coverage = 0.0;
+                }
+                PRIMITIVE_FILL_LINEAR_GRADIENT => {
+                    let p0 = self.half2(base_vertex);
+                    let p1 = self.half2(base_vertex + 1);
+                    base_vertex += 2;
+
+                    let dir = p1 - p0;
+                    let t = glm::dot(&glm::normalize(&dir), &(pos_world - p0)) / glm::length(&dir);
+                    let brush = self.gradient_eval(&mut base_vertex, t);
+                    color = brush * (clip * fill_coverage(coverage, primitive));
+                    coverage = 0.0;
+                }
+                PRIMITIVE_FILL_RADIAL_GRADIENT => {
+                    let center = self.half2(base_vertex);
+                    let focal = self.half2(base_vertex + 1);
+                    let radius = self.float(base_vertex + 2);
+                    base_vertex += 3;
+
+                    let d = center - focal;
+                    let q = pos_world - focal;
+                    let a = glm::dot(&d, &d) - radius * radius;
+                    let b = glm::dot(&q, &d);
+                    let c = glm::dot(&q, &q);
+                    let t = (b - (b * b - a * c).max(0.0).sqrt()) / a;
+                    let brush = self.gradient_eval(&mut base_vertex, t);
+                    color = brush * (clip * fill_coverage(coverage, primitive));
+                    coverage = 0.0;
+                }
+                PRIMITIVE_FILL_SWEEP_GRADIENT => {
+                    let center = self.half2(base_vertex);
+                    let start_angle = self.float(base_vertex + 1);
+                    let end_angle = self.float(base_vertex + 2);
+                    base_vertex += 3;
+
+                    let q = pos_world - center;
+                    let angle = start_angle + glsl_mod(q.y.atan2(q.x) - start_angle, 2.0 * PI);
+                    let t = (angle - start_angle) / (end_angle - start_angle);
+                    let brush = self.gradient_eval(&mut base_vertex, t);
+                    color = brush * (clip * fill_coverage(coverage, primitive));
+                    coverage = 0.0;
+                }
+                PRIMITIVE_FILL_IMAGE => {
+                    let image_id = self.vertices[base_vertex] as usize;
+                    let mode = self.vertices[base_vertex + 1];
+                    let sampling = mode & 0xFF;
+                    let extend = mode >> 8;
+
+                    let m0 = glm::vec2(self.float(base_vertex + 2), self.float(base_vertex + 3));
+                    let m1 = glm::vec2(self.float(base_vertex + 4), self.float(base_vertex + 5));
+                    let t = glm::vec2(self.float(base_vertex + 6), self.float(base_vertex + 7));
+                    base_vertex += 8;
+
+                    let p = m0 * pos_world.x + m1 * pos_world.y + t;
+
+                    // unknown images and empty rects are transparent
+                    let rect = match self.atlas_rects.get(4 * image_id..4 * image_id + 4) {
+                        Some(rect) => [rect[0], rect[1], rect[2], rect[3]],
+                        None => [0; 4],
+                    };
+
+                    let texel = if rect[2] == 0 || rect[3] == 0 {
+                        glm::vec4(0.0, 0.0, 0.0, 0.0)
+                    } else if sampling == SAMPLING_NEAREST {
+                        self.image_texel(rect, p.x.floor() as i32, p.y.floor() as i32, extend)
+                    } else {
+                        let s = p - glm::vec2(0.5, 0.5);
+                        let (ix, iy) = (s.x.floor() as i32, s.y.floor() as i32);
+                        let (fx, fy) = (fract(s.x), fract(s.y));
+                        mix4(
+                            mix4(
+                                self.image_texel(rect, ix, iy, extend),
+                                self.image_texel(rect, ix + 1, iy, extend),
+                                fx,
+                            ),
+                            mix4(
+                                self.image_texel(rect, ix, iy + 1, extend),
+                                self.image_texel(rect, ix + 1, iy + 1, extend),
+                                fx,
+                            ),
+                            fy,
+                        )
+                    };
+
+                    color = texel * (clip * fill_coverage(coverage, primitive));
+                    coverage = 0.0;
+                }
+                _ => (),
+            }
+        }
+
+        // `BlendOp::Min` keeps the backdrop where the source is white
+        if prim_start < prim_end && self.primitives[prim_end as usize - 1] & FILL_OVER_WHITE != 0 {
+            color = over_white(color);
+        }
+
+        color
+    }
+}
+
+fn clamp(x: f32, min: f32, max: f32) -> f32 {
+    x.max(min).min(max)
+}
+
+fn mix(a: f32, b: f32, t: f32) -> f32 {
+    a * (1.0 - t) + b * t
+}
+
+fn mix4(a: glm::Vec4, b: glm::Vec4, t: f32) -> glm::Vec4 {
+    a * (1.0 - t) + b * t
+}
+
+fn fract(x: f32) -> f32 {
+    x - x.floor()
+}
+
+// GLSL `mod`, result has the sign of `y`
+fn glsl_mod(x: f32, y: f32) -> f32 {
+    x - y * (x / y).floor()
+}
+
+// GLSL `sign`, zero for zero
+fn sign(x: f32) -> f32 {
+    if x > 0.0 {
+        1.0
+    } else if x < 0.0 {
+        -1.0
+    } else {
+        0.0
+    }
+}
+
+fn line_raycast(p0: f32, p1: f32, p: f32) -> f32 {
+    (p - p0) / (p1 - p0)
+}
+
+fn quad_eval(p0: f32, p1: f32, p2: f32, t: f32) -> f32 {
+    (1.0 - t) * (1.0 - t) * p0 + 2.0 * t * (1.0 - t) * p1 + t * t * p2
+}
+
+fn quad_raycast(p0: f32, p1: f32, p2: f32, t: f32) -> f32 {
+    let a = p0 - 2.0 * p1 + p2;
+
+    if a.abs() < 0.0001 {
+        return line_raycast(p0, p2, t);
+    }
+
+    let b = p0 - p1;
+    let c = p0 - t;
+    let dscr_sq = b * b - a * c;
+    let sign = (p2 > t) as i32 - (p0 > t) as i32;
+
+    (b + sign as f32 * dscr_sq.sqrt()) / a
+}
+
+fn erf(x: f32) -> f32 {
+    let s = sign(x);
+    let a = x.abs();
+    let mut x = 1.0 + (0.278393 + (0.230389 + 0.078108 * (a * a)) * a) * a;
+    x *= x;
+    s - s / (x * x)
+}
+
+fn rounded_edge(x: f32, x0: f32, x1: f32, r0: f32, r1: f32) -> glm::Vec2 {
+    let mut dx = 0.0;
+    let mut r = 0.0;
+    if x < x0 + r0 {
+        dx = x0 + r0 - x;
+        r = r0;
+    } else if x > x1 - r1 {
+        dx = x - (x1 - r1);
+        r = r1;
+    }
+
+    if r == 0.0 {
+        return glm::vec2(0.0, 1.0);
+    }
+
+    let dy = (r * r - dx * dx).max(0.0).sqrt();
+    glm::vec2(r - dy, dy / r)
+}
+
+fn gaussian(x: f32, sigma: f32) -> f32 {
+    (-(x * x) / (2.0 * sigma * sigma)).exp() / ((2.0 * PI).sqrt() * sigma)
+}
+
+fn rounded_shadow_x(x: f32, y: f32, sigma: f32, radius: f32, half_extent: glm::Vec2) -> f32 {
+    let delta = (half_extent.y - radius - y.abs()).min(0.0);
+    let curved = half_extent.x - radius + (radius * radius - delta * delta).max(0.0).sqrt();
+    let norm = 0.5f32.sqrt() / sigma;
+    0.5 * (erf((x + curved) * norm) - erf((x - curved) * norm))
+}
+
+fn rounded_shadow(center: glm::Vec2, half_extent: glm::Vec2, sigma: f32, radius: f32) -> f32 {
+    let p = -center;
+    let low = p.y - half_extent.y;
+    let high = p.y + half_extent.y;
+    let start = clamp(-3.0 * sigma, low, high);
+    let end = clamp(3.0 * sigma, low, high);
+    let step = (end - start) / 4.0;
+
+    let mut y = start + 0.5 * step;
+    let mut value = 0.0;
+    for _ in 0..4 {
+        value +=
+            rounded_shadow_x(p.x, p.y - y, sigma, radius, half_extent) * gaussian(y, sigma) * step;
+        y += step;
+    }
+    value
+}
+
+fn cdf(x: f32, m: f32) -> f32 {
+    clamp(x * m + 0.5, 0.0, 1.0)
+}
+
+fn fill_coverage(coverage: f32, primitive: u32) -> f32 {
+    if primitive & FILL_RULE_EVEN_ODD != 0 {
+        return 1.0 - (1.0 - glsl_mod(coverage, 2.0)).abs();
+    }
+    coverage.abs().min(1.0)
+}
+
+fn image_extend(x: i32, size: i32, extend: u32) -> i32 {
+    match extend {
+        SPREAD_REPEAT => x.rem_euclid(size),
+        SPREAD_REFLECT => {
+            let m = x.rem_euclid(2 * size);
+            if m < size {
+                m
+            } else {
+                2 * size - 1 - m
+            }
+        }
+        _ => clamp(x as f32, 0.0, (size - 1) as f32) as i32,
+    }
+}
+
+// Fixed-function blending of premultiplied colors, clamped like a normalized target.
+fn blend(blend_mode: BlendMode, src: glm::Vec4, dst: glm::Vec4) -> glm::Vec4 {
+    let state = blend_mode.state();
+    let channel = |channel: BlendChannel, i: usize| {
+        let factor = |factor| match factor {
+            BlendFactor::Zero => 0.0,
+            BlendFactor::One => 1.0,
+            BlendFactor::OneMinusSrcColor => 1.0 - src[i],
+            BlendFactor::SrcAlpha => src.w,
+            BlendFactor::OneMinusSrcAlpha => 1.0 - src.w,
+            BlendFactor::DstColor => dst[i],
+            BlendFactor::DstAlpha => dst.w,
+            BlendFactor::OneMinusDstAlpha => 1.0 - dst.w,
+        };
+        let value = match channel.op {
+            BlendOp::Add => src[i] * factor(channel.src) + dst[i] * factor(channel.dst),
+            BlendOp::Min => src[i].min(dst[i]),
+            BlendOp::Max => src[i].max(dst[i]),
+        };
+        clamp(value, 0.0, 1.0)
+    };
+
+    glm::vec4(
+        channel(state.color, 0),
+        channel(state.color, 1),
+        channel(state.color, 2),
+        channel(state.alpha, 3),
+    )
+}
+
+// premultiplied `color` composited over opaque white, alpha is kept
+fn over_white(color: glm::Vec4) -> glm::Vec4 {
+    let white = 1.0 - color.w;
+    glm::vec4(color.x + white, color.y + white, color.z + white, color.w)
+}
+
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+fn unorm8(x: f32) -> u8 {
+    (clamp(x, 0.0, 1.0) * 255.0 + 0.5) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Brush, Curve, FillRule, Rect};
+
+    // world units map to pixels, y pointing up
+    fn pixel_viewport(size: u32) -> Viewport {
+        Viewport {
+            position: (0.5 * size as f32, 0.5 * size as f32),
+            scaling_y: size as f32,
+            aspect_ratio: 1.0,
+        }
+    }
+
+    fn vertex(x: f32, y: f32) -> BboxVertex {
+        BboxVertex {
+            pos_world: glm::vec2(x, y),
+            pos_curve: glm::vec2(x, y),
+            curve_range: [0, 0, 0],
+        }
+    }
+
+    #[test]
+    fn top_left_rule() {
+        let rasterizer = Rasterizer {
+            viewport: pixel_viewport(8).get_rect(),
+            width: 8,
+            height: 8,
+        };
+
+        // triangle fan whose edges pass through pixel centers
+        let grid = [0.0, 3.5, 8.0];
+        let mut hits = [0u32; 64];
+        for i in 0..2 {
+            for j in 0..2 {
+                let (x0, x1) = (grid[i], grid[i + 1]);
+                let (y0, y1) = (grid[j], grid[j + 1]);
+                let triangles = [
+                    [vertex(x0, y0), vertex(x1, y1), vertex(x1, y0)],
+                    [vertex(x0, y0), vertex(x0, y1), vertex(x1, y1)],
+                ];
+                for triangle in &triangles {
+                    rasterizer.draw_triangle(triangle, |x, y, _| hits[(y * 8 + x) as usize] += 1);
+                }
+            }
+        }
+
+        assert!(hits.iter().all(|&n| n == 1), "{:?}", hits);
+    }
+
+    #[test]
+    fn rect_coverage() {
+        let mut gpu_data = GpuData::new();
+        let p0 = glm::vec2(2.0, 1.0);
+        let p1 = glm::vec2(6.5, 7.0);
+        gpu_data.extend(
+            &[Curve::Rect { p0, p1 }],
+            Rect {
+                offset_local: p0,
+                extent_local: p1 - p0,
+                offset_curve: p0,
+                extent_curve: p1 - p0,
+            }
+            .extrude(1.0),
+            &Brush::Color([255, 255, 255, 255]),
+            FillRule::NonZero,
+        );
+
+        let image = render(&gpu_data, &pixel_viewport(8), 8, 8);
+        let alpha = |x: u32, y: u32| image.get_pixel(x, 7 - y).0[3];
+
+        // inside, outside and on the right edge through the pixel centers
+        assert_eq!(alpha(4, 4), 255);
+        assert_eq!(alpha(1, 4), 0);
+        assert_eq!(alpha(7, 4), 0);
+        assert_eq!(alpha(4, 7), 0);
+        assert!((alpha(6, 4) as i32 - 128).abs() <= 1, "{}", alpha(6, 4));
+    }
+}
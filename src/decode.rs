@@ -0,0 +1,915 @@
+use crate::gpu::{
+    scale_factor, FILL_RULE_EVEN_ODD, PRIMITIVE_ARC, PRIMITIVE_CIRCLE, PRIMITIVE_CLIP,
+    PRIMITIVE_FILL_COLOR, PRIMITIVE_FILL_IMAGE, PRIMITIVE_FILL_LINEAR_GRADIENT,
+    PRIMITIVE_FILL_RADIAL_GRADIENT, PRIMITIVE_FILL_SWEEP_GRADIENT, PRIMITIVE_LINE, PRIMITIVE_MASK,
+    PRIMITIVE_QUADRATIC, PRIMITIVE_RECT, PRIMITIVE_ROUNDED_RECT, PRIMITIVE_SHADOW_RECT,
+    SAMPLING_NEAREST, SPREAD_REFLECT, SPREAD_REPEAT,
+};
+use crate::{
+    glm, Brush, Color, Curve, DrawCommand, FillRule, GpuData, GradientStop, Rect, Sampling,
+    SpreadMode, Transform,
+};
+use std::error::Error;
+use std::fmt;
+
+/// Problem found while decoding a draw, see `GpuData::decode`.
+///
+/// Primitive indices refer to `GpuData::primitives`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum DecodeError {
+    /// Fewer than 6 `bbox` vertices or missing `curve_ranges` entries.
+    IncompleteDraw,
+    /// The vertices of the draw reference different curve ranges.
+    InconsistentCurveRange,
+    /// NaN in the `bbox` vertices.
+    NaNBbox,
+    /// `start..end` is out of range of `primitives`.
+    PrimitiveRange {
+        start: u32,
+        end: u32,
+    },
+    /// Operands of the primitive at `index` extend past the end of `vertices`.
+    VertexRange {
+        index: u32,
+    },
+    UnknownPrimitive {
+        index: u32,
+        primitive: u32,
+    },
+    /// NaN in the operands of the primitive at `index`.
+    NaN {
+        index: u32,
+    },
+    MissingFill,
+    /// Primitives following the fill at `index` are never evaluated.
+    TrailingPrimitives {
+        index: u32,
+    },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DecodeError::IncompleteDraw => write!(f, "incomplete bbox or curve range"),
+            DecodeError::InconsistentCurveRange => {
+                write!(f, "vertices reference different curve ranges")
+            }
+            DecodeError::NaNBbox => write!(f, "NaN in bbox"),
+            DecodeError::PrimitiveRange { start, end } => {
+                write!(f, "primitive range {}..{} out of bounds", start, end)
+            }
+            DecodeError::VertexRange { index } => {
+                write!(f, "primitive {} reads past the end of the vertices", index)
+            }
+            DecodeError::UnknownPrimitive { index, primitive } => {
+                write!(f, "unknown primitive {:#x} at {}", primitive, index)
+            }
+            DecodeError::NaN { index } => write!(f, "NaN in operands of primitive {}", index),
+            DecodeError::MissingFill => write!(f, "missing fill primitive"),
+            DecodeError::TrailingPrimitives { index } => {
+                write!(f, "primitives after the fill at {} are ignored", index)
+            }
+        }
+    }
+}
+
+impl Error for DecodeError {}
+
+/// Draw reconstructed from the packed `GpuData` streams, one per `extend` call.
+///
+/// Curves are in the curve space of `bbox` with the precision of the encoding
+/// (f16 coordinates), brushes in local space.
+#[derive(Debug, Clone)]
+pub struct DecodedDraw {
+    pub bbox: Rect,
+    /// Offset of the operands in `GpuData::vertices`.
+    pub vertex_start: u32,
+    /// Index of the first primitive in `GpuData::primitives`.
+    pub primitive_start: u32,
+    /// Primitives of the draw including flags.
+    pub opcodes: Vec<u32>,
+    /// Clip paths in the order they were pushed.
+    pub clips: Vec<(Vec<Curve>, FillRule)>,
+    pub curves: Vec<Curve>,
+    /// `None` if the stream has no valid fill primitive.
+    pub brush: Option<Brush>,
+    pub fill_rule: FillRule,
+    pub errors: Vec<DecodeError>,
+}
+
+impl DecodedDraw {
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// Maps curve coordinates back into local space, inverse of `Rect::local_to_curve`.
+    pub fn curve_to_local(&self) -> Transform {
+        let rect = &self.bbox;
+        Transform::translate(-rect.offset_curve)
+            .then(&Transform::scale(
+                scale_factor(rect.extent_local.x, rect.extent_curve.x),
+                scale_factor(rect.extent_local.y, rect.extent_curve.y),
+            ))
+            .then(&Transform::translate(rect.offset_local))
+    }
+}
+
+fn unpack_f16x2(x: u32) -> glm::Vec2 {
+    glm::vec2(
+        half::f16::from_bits(x as u16).to_f32(),
+        half::f16::from_bits((x >> 16) as u16).to_f32(),
+    )
+}
+
+// premultiplied to straight alpha, inverse of `pack_color`
+fn unpack_color(x: u32) -> Color {
+    let a = (x >> 24) as u8;
+    let unpremultiply = |c: u32| {
+        let c = c & 0xFF;
+        if a == 0 {
+            0
+        } else {
+            ((c * 255 + a as u32 / 2) / a as u32).min(255) as u8
+        }
+    };
+    [
+        unpremultiply(x),
+        unpremultiply(x >> 8),
+        unpremultiply(x >> 16),
+        a,
+    ]
+}
+
+fn unpack_spread(x: u32) -> SpreadMode {
+    match x {
+        SPREAD_REPEAT => SpreadMode::Repeat,
+        SPREAD_REFLECT => SpreadMode::Reflect,
+        _ => SpreadMode::Pad,
+    }
+}
+
+fn unpack_fill_rule(primitive: u32) -> FillRule {
+    if primitive & FILL_RULE_EVEN_ODD != 0 {
+        FillRule::EvenOdd
+    } else {
+        FillRule::NonZero
+    }
+}
+
+// Sequential reader over the vertex stream of a draw.
+struct Reader<'a> {
+    vertices: &'a [u32],
+    pos: usize,
+    // set if any float operand read so far is NaN
+    nan: bool,
+}
+
+impl<'a> Reader<'a> {
+    fn u32(&mut self) -> Option<u32> {
+        let x = *self.vertices.get(self.pos)?;
+        self.pos += 1;
+        Some(x)
+    }
+
+    fn f32(&mut self) -> Option<f32> {
+        let x = f32::from_bits(self.u32()?);
+        self.nan |= x.is_nan();
+        Some(x)
+    }
+
+    fn f16x2(&mut self) -> Option<glm::Vec2> {
+        let p = unpack_f16x2(self.u32()?);
+        self.nan |= p.x.is_nan() || p.y.is_nan();
+        Some(p)
+    }
+
+    fn curve(&mut self, primitive: u32) -> Option<Curve> {
+        let curve = match primitive {
+            PRIMITIVE_LINE => Curve::Line {
+                p0: self.f16x2()?,
+                p1: self.f16x2()?,
+            },
+            PRIMITIVE_QUADRATIC => Curve::Quad {
+                p0: self.f16x2()?,
+                p1: self.f16x2()?,
+                p2: self.f16x2()?,
+            },
+            PRIMITIVE_CIRCLE => Curve::Circle {
+                center: self.f16x2()?,
+                radius: self.f32()?,
+            },
+            PRIMITIVE_ARC => {
+                let center = self.f16x2()?;
+                Curve::Arc {
+                    center,
+                    p0: center + self.f16x2()?,
+                    p1: center + self.f16x2()?,
+                }
+            }
+            PRIMITIVE_RECT => Curve::Rect {
+                p0: self.f16x2()?,
+                p1: self.f16x2()?,
+            },
+            PRIMITIVE_ROUNDED_RECT => {
+                let p0 = self.f16x2()?;
+                let p1 = self.f16x2()?;
+                let lower = self.f16x2()?;
+                let upper = self.f16x2()?;
+
+                // stored as lower-left, lower-right, upper-right, upper-left, see `push_curve`
+                let sorted = [lower.x, lower.y, upper.x, upper.y];
+                let flip_x = p0.x > p1.x;
+                let flip_y = p0.y > p1.y;
+                let corners = [
+                    (flip_x, flip_y),
+                    (!flip_x, flip_y),
+                    (!flip_x, !flip_y),
+                    (flip_x, !flip_y),
+                ];
+
+                let mut radii = [0.0; 4];
+                for (i, &corner) in corners.iter().enumerate() {
+                    let slot = match corner {
+                        (false, false) => 0,
+                        (true, false) => 1,
+                        (true, true) => 2,
+                        (false, true) => 3,
+                    };
+                    radii[i] = sorted[slot];
+                }
+
+                Curve::RoundedRect { p0, p1, radii }
+            }
+            PRIMITIVE_SHADOW_RECT => {
+                // spread is already applied to the rect and radius
+                let p0 = self.f16x2()?;
+                let p1 = self.f16x2()?;
+                let sigma = self.f32()?;
+                let radius = self.f32()?;
+                Curve::ShadowRect {
+                    p0,
+                    p1,
+                    radius,
+                    sigma,
+                    spread: 0.0,
+                }
+            }
+            _ => return None,
+        };
+        Some(curve)
+    }
+
+    fn gradient_stops(&mut self) -> Option<(Vec<GradientStop>, SpreadMode)> {
+        let header = self.u32()?;
+        let num_stops = (header & 0xFFFF) as usize;
+        let mut stops = Vec::with_capacity(num_stops);
+        for _ in 0..num_stops {
+            stops.push(GradientStop {
+                offset: self.f32()?,
+                color: unpack_color(self.u32()?),
+            });
+        }
+        Some((stops, unpack_spread(header >> 16)))
+    }
+
+    fn brush(&mut self, primitive: u32) -> Option<Brush> {
+        let brush = match primitive {
+            PRIMITIVE_FILL_COLOR => Brush::Color(unpack_color(self.u32()?)),
+            PRIMITIVE_FILL_LINEAR_GRADIENT => {
+                let start = self.f16x2()?;
+                let end = self.f16x2()?;
+                let (stops, spread) = self.gradient_stops()?;
+                Brush::LinearGradient {
+                    start,
+                    end,
+                    stops,
+                    spread,
+                }
+            }
+            PRIMITIVE_FILL_RADIAL_GRADIENT => {
+                let center = self.f16x2()?;
+                let focal = self.f16x2()?;
+                let radius = self.f32()?;
+                let (stops, spread) = self.gradient_stops()?;
+                Brush::RadialGradient {
+                    center,
+                    radius,
+                    focal,
+                    stops,
+                    spread,
+                }
+            }
+            PRIMITIVE_FILL_SWEEP_GRADIENT => {
+                let center = self.f16x2()?;
+                let start_angle = self.f32()?;
+                let end_angle = self.f32()?;
+                let (stops, _) = self.gradient_stops()?;
+                Brush::SweepGradient {
+                    center,
+                    start_angle,
+                    end_angle,
+                    stops,
+                }
+            }
+            PRIMITIVE_FILL_IMAGE => {
+                let image_id = self.u32()?;
+                let mode = self.u32()?;
+                let m = [self.f32()?, self.f32()?, self.f32()?, self.f32()?];
+                let t = glm::vec2(self.f32()?, self.f32()?);
+
+                // stored transform maps world positions into texel space
+                let inverse = Transform {
+                    matrix: glm::mat2(m[0], m[2], m[1], m[3]),
+                    translation: t,
+                };
+                Brush::Image {
+                    image_id,
+                    transform: inverse.inverse().unwrap_or_else(Transform::identity),
+                    sampling: if mode & 0xFF == SAMPLING_NEAREST {
+                        Sampling::Nearest
+                    } else {
+                        Sampling::Linear
+                    },
+                    extend: unpack_spread((mode >> 8) & 0xFF),
+                }
+            }
+            _ => return None,
+        };
+        Some(brush)
+    }
+}
+
+impl GpuData {
+    /// Reconstruct the draws from the packed streams, one entry per 6 `bbox` vertices.
+    ///
+    /// Decoding is lenient, invalid streams are reported in `DecodedDraw::errors`.
+    pub fn decode(&self) -> Vec<DecodedDraw> {
+        let num_vertices = self.bbox.len() / 4;
+        (0..(num_vertices + 5) / 6)
+            .map(|draw| self.decode_draw(6 * draw))
+            .collect()
+    }
+
+    fn decode_draw(&self, vertex: usize) -> DecodedDraw {
+        let mut draw = DecodedDraw {
+            bbox: Rect {
+                offset_local: glm::vec2(0.0, 0.0),
+                extent_local: glm::vec2(0.0, 0.0),
+                offset_curve: glm::vec2(0.0, 0.0),
+                extent_curve: glm::vec2(0.0, 0.0),
+            },
+            vertex_start: 0,
+            primitive_start: 0,
+            opcodes: Vec::new(),
+            clips: Vec::new(),
+            curves: Vec::new(),
+            brush: None,
+            fill_rule: FillRule::NonZero,
+            errors: Vec::new(),
+        };
+
+        let (bbox, ranges) = match (
+            self.bbox.get(4 * vertex..4 * (vertex + 6)),
+            self.curve_ranges.get(3 * vertex..3 * (vertex + 6)),
+        ) {
+            (Some(bbox), Some(ranges)) => (bbox, ranges),
+            _ => {
+                draw.errors.push(DecodeError::IncompleteDraw);
+                return draw;
+            }
+        };
+
+        // first and third vertex span the rect, see `extend`
+        draw.bbox = Rect {
+            offset_local: glm::vec2(bbox[0], bbox[1]),
+            extent_local: glm::vec2(bbox[8] - bbox[0], bbox[9] - bbox[1]),
+            offset_curve: glm::vec2(bbox[2], bbox[3]),
+            extent_curve: glm::vec2(bbox[10] - bbox[2], bbox[11] - bbox[3]),
+        };
+        if bbox.iter().any(|x| x.is_nan()) {
+            draw.errors.push(DecodeError::NaNBbox);
+        }
+        if ranges.chunks(3).any(|range| range != &ranges[..3]) {
+            draw.errors.push(DecodeError::InconsistentCurveRange);
+        }
+
+        let (start, end) = (ranges[1], ranges[2]);
+        draw.vertex_start = ranges[0];
+        draw.primitive_start = start;
+        let opcodes = match self.primitives.get(start as usize..end as usize) {
+            Some(primitives) => primitives,
+            None => {
+                draw.errors.push(DecodeError::PrimitiveRange { start, end });
+                return draw;
+            }
+        };
+        draw.opcodes = opcodes.to_vec();
+
+        let mut reader = Reader {
+            vertices: &self.vertices,
+            pos: draw.vertex_start as usize,
+            nan: false,
+        };
+        let mut curves = Vec::new();
+        let mut truncated = false;
+        for (i, &primitive) in opcodes.iter().enumerate() {
+            let index = start + i as u32;
+            if draw.brush.is_some() {
+                draw.errors.push(DecodeError::TrailingPrimitives { index });
+                break;
+            }
+
+            let decoded = match primitive & PRIMITIVE_MASK {
+                PRIMITIVE_CLIP => {
+                    let curves = std::mem::take(&mut curves);
+                    draw.clips.push((curves, unpack_fill_rule(primitive)));
+                    Some(())
+                }
+                PRIMITIVE_FILL_COLOR..=PRIMITIVE_FILL_IMAGE => {
+                    reader.brush(primitive & PRIMITIVE_MASK).map(|brush| {
+                        draw.brush = Some(brush);
+                        draw.fill_rule = unpack_fill_rule(primitive);
+                    })
+                }
+                PRIMITIVE_LINE..=PRIMITIVE_ROUNDED_RECT if primitive & !PRIMITIVE_MASK == 0 => {
+                    reader.curve(primitive).map(|curve| curves.push(curve))
+                }
+                _ => {
+                    draw.errors
+                        .push(DecodeError::UnknownPrimitive { index, primitive });
+                    truncated = true;
+                    break;
+                }
+            };
+
+            if decoded.is_none() {
+                draw.errors.push(DecodeError::VertexRange { index });
+                truncated = true;
+                break;
+            }
+            if reader.nan {
+                draw.errors.push(DecodeError::NaN { index });
+                reader.nan = false;
+            }
+        }
+        draw.curves = curves;
+
+        if draw.brush.is_none() && !truncated {
+            draw.errors.push(DecodeError::MissingFill);
+        }
+
+        draw
+    }
+}
+
+fn opcode_name(primitive: u32) -> &'static str {
+    match primitive & PRIMITIVE_MASK {
+        PRIMITIVE_LINE => "LINE",
+        PRIMITIVE_QUADRATIC => "QUADRATIC",
+        PRIMITIVE_CIRCLE => "CIRCLE",
+        PRIMITIVE_ARC => "ARC",
+        PRIMITIVE_RECT => "RECT",
+        PRIMITIVE_SHADOW_RECT => "SHADOW_RECT",
+        PRIMITIVE_ROUNDED_RECT => "ROUNDED_RECT",
+        PRIMITIVE_FILL_COLOR => "FILL_COLOR",
+        PRIMITIVE_FILL_LINEAR_GRADIENT => "FILL_LINEAR_GRADIENT",
+        PRIMITIVE_FILL_RADIAL_GRADIENT => "FILL_RADIAL_GRADIENT",
+        PRIMITIVE_FILL_SWEEP_GRADIENT => "FILL_SWEEP_GRADIENT",
+        PRIMITIVE_FILL_IMAGE => "FILL_IMAGE",
+        PRIMITIVE_CLIP => "CLIP",
+        _ => "?",
+    }
+}
+
+fn fill_rule_name(fill_rule: FillRule) -> &'static str {
+    match fill_rule {
+        FillRule::NonZero => "nonzero",
+        FillRule::EvenOdd => "evenodd",
+    }
+}
+
+struct Point(glm::Vec2);
+
+impl fmt::Display for Point {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "({}, {})", self.0.x, self.0.y)
+    }
+}
+
+// Operands of a curve primitive.
+fn fmt_curve(f: &mut fmt::Formatter, curve: &Curve) -> fmt::Result {
+    match *curve {
+        Curve::Line { p0, p1 } => write!(f, "{} {}", Point(p0), Point(p1)),
+        Curve::Quad { p0, p1, p2 } => write!(f, "{} {} {}", Point(p0), Point(p1), Point(p2)),
+        Curve::Circle { center, radius } => write!(f, "center {} radius {}", Point(center), radius),
+        Curve::Arc { center, p0, p1 } => {
+            write!(f, "center {} {} {}", Point(center), Point(p0), Point(p1))
+        }
+        Curve::Rect { p0, p1 } => write!(f, "{} {}", Point(p0), Point(p1)),
+        Curve::RoundedRect { p0, p1, radii } => {
+            write!(f, "{} {} radii {:?}", Point(p0), Point(p1), radii)
+        }
+        Curve::ShadowRect {
+            p0,
+            p1,
+            radius,
+            sigma,
+            ..
+        } => write!(
+            f,
+            "{} {} radius {} sigma {}",
+            Point(p0),
+            Point(p1),
+            radius,
+            sigma
+        ),
+        // not part of the encoding
+        Curve::Cubic { .. } | Curve::Ellipse { .. } => write!(f, "{:?}", curve),
+    }
+}
+
+fn fmt_stops(f: &mut fmt::Formatter, stops: &[GradientStop], spread: SpreadMode) -> fmt::Result {
+    write!(f, " {:?}", spread)?;
+    for stop in stops {
+        write!(f, " {}:{:?}", stop.offset, stop.color)?;
+    }
+    Ok(())
+}
+
+// Operands of a fill primitive, colors are straight alpha.
+fn fmt_brush(f: &mut fmt::Formatter, brush: &Brush) -> fmt::Result {
+    match *brush {
+        Brush::Color(color) => write!(f, "{:?}", color),
+        Brush::LinearGradient {
+            start,
+            end,
+            ref stops,
+            spread,
+        } => {
+            write!(f, "{} {}", Point(start), Point(end))?;
+            fmt_stops(f, stops, spread)
+        }
+        Brush::RadialGradient {
+            center,
+            radius,
+            focal,
+            ref stops,
+            spread,
+        } => {
+            write!(
+                f,
+                "center {} radius {} focal {}",
+                Point(center),
+                radius,
+                Point(focal)
+            )?;
+            fmt_stops(f, stops, spread)
+        }
+        Brush::SweepGradient {
+            center,
+            start_angle,
+            end_angle,
+            ref stops,
+        } => {
+            write!(
+                f,
+                "center {} angles {} {}",
+                Point(center),
+                start_angle,
+                end_angle
+            )?;
+            fmt_stops(f, stops, SpreadMode::Pad)
+        }
+        Brush::Image {
+            image_id,
+            ref transform,
+            sampling,
+            extend,
+        } => {
+            let m = transform.matrix;
+            write!(
+                f,
+                "image {} {:?} {:?} matrix [{}, {}; {}, {}] translation {}",
+                image_id,
+                sampling,
+                extend,
+                m[(0, 0)],
+                m[(0, 1)],
+                m[(1, 0)],
+                m[(1, 1)],
+                Point(transform.translation)
+            )
+        }
+    }
+}
+
+/// Listing of the primitives with their decoded operands, followed by the errors.
+impl fmt::Display for DecodedDraw {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let rect = &self.bbox;
+        write!(
+            f,
+            "bbox local {} extent {} curve {} extent {}, vertices {}..",
+            Point(rect.offset_local),
+            Point(rect.extent_local),
+            Point(rect.offset_curve),
+            Point(rect.extent_curve),
+            self.vertex_start
+        )?;
+
+        // primitives are decoded in stream order: clip curves, clip, curves, fill
+        let mut curves = self
+            .clips
+            .iter()
+            .flat_map(|(curves, _)| curves)
+            .chain(&self.curves);
+        let mut clips = self.clips.iter();
+        let mut brush = self.brush.as_ref();
+
+        for (i, &primitive) in self.opcodes.iter().enumerate() {
+            write!(
+                f,
+                "\n  {:>6}: {:#06x} {}",
+                self.primitive_start as usize + i,
+                primitive,
+                opcode_name(primitive)
+            )?;
+            match primitive & PRIMITIVE_MASK {
+                PRIMITIVE_CLIP => {
+                    if let Some((_, fill_rule)) = clips.next() {
+                        write!(f, " {}", fill_rule_name(*fill_rule))?;
+                    }
+                }
+                PRIMITIVE_FILL_COLOR..=PRIMITIVE_FILL_IMAGE => {
+                    if let Some(brush) = brush.take() {
+                        write!(f, " {} ", fill_rule_name(self.fill_rule))?;
+                        fmt_brush(f, brush)?;
+                    }
+                }
+                PRIMITIVE_LINE..=PRIMITIVE_ROUNDED_RECT if primitive & !PRIMITIVE_MASK == 0 => {
+                    if let Some(curve) = curves.next() {
+                        write!(f, " ")?;
+                        fmt_curve(f, curve)?;
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        for error in &self.errors {
+            write!(f, "\n  error: {}", error)?;
+        }
+        Ok(())
+    }
+}
+
+/// Disassembly of the draw commands and the draws they reference.
+impl fmt::Display for GpuData {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let draws = self.decode();
+        let num_vertices = self.bbox.len() as u32 / 4;
+
+        let mut depth = 0;
+        for command in &self.commands {
+            match *command {
+                DrawCommand::Draw {
+                    start,
+                    end,
+                    blend_mode,
+                } => {
+                    writeln!(f, "draw {}..{} {:?}", start, end, blend_mode)?;
+                    if start % 6 != 0 || end % 6 != 0 || start > end || end > num_vertices {
+                        writeln!(
+                            f,
+                            "error: {}..{} isn't a range of whole draws in {} bbox vertices",
+                            start, end, num_vertices
+                        )?;
+                    }
+                    for i in start as usize / 6..(end as usize + 5) / 6 {
+                        if let Some(draw) = draws.get(i) {
+                            writeln!(f, "#{} {}", i, draw)?;
+                        }
+                    }
+                }
+                DrawCommand::PushLayer {
+                    opacity,
+                    blend_mode,
+                } => {
+                    depth += 1;
+                    writeln!(f, "push_layer {} {:?}", opacity, blend_mode)?;
+                }
+                DrawCommand::PopLayer => {
+                    if depth == 0 {
+                        writeln!(f, "pop_layer\nerror: no layer to pop, ignored")?;
+                    } else {
+                        depth -= 1;
+                        writeln!(f, "pop_layer")?;
+                    }
+                }
+            }
+        }
+
+        if depth > 0 {
+            writeln!(f, "error: {} layers not popped, closed at the end", depth)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(p0: glm::Vec2, p1: glm::Vec2) -> Rect {
+        Rect {
+            offset_local: p0,
+            extent_local: p1 - p0,
+            offset_curve: p0,
+            extent_curve: p1 - p0,
+        }
+    }
+
+    fn scene() -> GpuData {
+        let mut gpu_data = GpuData::new();
+        gpu_data.extend(
+            &[
+                Curve::Line {
+                    p0: glm::vec2(0.0, 0.0),
+                    p1: glm::vec2(16.0, 32.0),
+                },
+                Curve::Circle {
+                    center: glm::vec2(8.0, 8.0),
+                    radius: 4.5,
+                },
+            ],
+            rect(glm::vec2(0.0, 0.0), glm::vec2(16.0, 32.0)),
+            &Brush::Color([10, 20, 30, 255]),
+            FillRule::EvenOdd,
+        );
+
+        gpu_data.push_clip(
+            &[Curve::Rect {
+                p0: glm::vec2(-8.0, -8.0),
+                p1: glm::vec2(64.0, 64.0),
+            }],
+            FillRule::NonZero,
+        );
+        gpu_data.extend(
+            &[Curve::Rect {
+                p0: glm::vec2(4.0, 4.0),
+                p1: glm::vec2(12.0, 20.0),
+            }],
+            rect(glm::vec2(4.0, 4.0), glm::vec2(12.0, 20.0)),
+            &Brush::LinearGradient {
+                start: glm::vec2(4.0, 4.0),
+                end: glm::vec2(12.0, 4.0),
+                stops: vec![
+                    GradientStop {
+                        offset: 0.0,
+                        color: [255, 0, 0, 255],
+                    },
+                    GradientStop {
+                        offset: 1.0,
+                        color: [0, 0, 255, 128],
+                    },
+                ],
+                spread: SpreadMode::Repeat,
+            },
+            FillRule::NonZero,
+        );
+        gpu_data.pop_clip();
+        gpu_data
+    }
+
+    #[test]
+    fn round_trip() {
+        let gpu_data = scene();
+        let draws = gpu_data.decode();
+        assert_eq!(draws.len(), 2);
+        assert!(draws.iter().all(|draw| draw.is_valid()), "{}", gpu_data);
+
+        assert_eq!(
+            format!("{:?}", draws[0].curves),
+            format!(
+                "{:?}",
+                [
+                    Curve::Line {
+                        p0: glm::vec2(0.0, 0.0),
+                        p1: glm::vec2(16.0, 32.0),
+                    },
+                    Curve::Circle {
+                        center: glm::vec2(8.0, 8.0),
+                        radius: 4.5,
+                    },
+                ]
+            )
+        );
+        assert_eq!(draws[0].brush, Some(Brush::Color([10, 20, 30, 255])));
+        assert_eq!(draws[0].fill_rule, FillRule::EvenOdd);
+        assert!(draws[0].clips.is_empty());
+
+        assert_eq!(draws[1].clips.len(), 1);
+        assert_eq!(draws[1].clips[0].1, FillRule::NonZero);
+        match draws[1].brush {
+            Some(Brush::LinearGradient {
+                ref stops, spread, ..
+            }) => {
+                assert_eq!(stops.len(), 2);
+                assert_eq!(stops[1].color, [0, 0, 255, 128]);
+                assert_eq!(spread, SpreadMode::Repeat);
+            }
+            ref brush => panic!("unexpected brush {:?}", brush),
+        }
+    }
+
+    #[test]
+    fn corrupt_primitives() {
+        let mut gpu_data = scene();
+        let start = gpu_data.decode()[0].primitive_start as usize;
+        gpu_data.primitives[start] = 0xEE;
+
+        let draws = gpu_data.decode();
+        assert_eq!(
+            draws[0].errors,
+            [DecodeError::UnknownPrimitive {
+                index: start as u32,
+                primitive: 0xEE,
+            }]
+        );
+        assert!(draws[1].is_valid());
+
+        // fill in the middle of the stream
+        let mut gpu_data = scene();
+        let start = gpu_data.decode()[0].primitive_start as usize;
+        gpu_data.primitives[start] = PRIMITIVE_FILL_COLOR;
+        let errors = &gpu_data.decode()[0].errors;
+        assert_eq!(
+            errors[0],
+            DecodeError::TrailingPrimitives {
+                index: start as u32 + 1
+            }
+        );
+
+        // stream without a fill
+        let mut gpu_data = scene();
+        let end = gpu_data.curve_ranges[2] as usize;
+        gpu_data.primitives[end - 1] = PRIMITIVE_LINE;
+        assert!(gpu_data.decode()[0]
+            .errors
+            .contains(&DecodeError::MissingFill));
+    }
+
+    #[test]
+    fn corrupt_curve_ranges() {
+        let mut gpu_data = scene();
+        gpu_data.curve_ranges[3 + 2] += 1;
+        assert_eq!(
+            gpu_data.decode()[0].errors,
+            [DecodeError::InconsistentCurveRange]
+        );
+
+        let mut gpu_data = scene();
+        let len = gpu_data.primitives.len() as u32;
+        for range in gpu_data.curve_ranges.chunks_mut(3).skip(6) {
+            range[2] = len + 1;
+        }
+        let draws = gpu_data.decode();
+        assert!(draws[0].is_valid());
+        assert_eq!(
+            draws[1].errors,
+            [DecodeError::PrimitiveRange {
+                start: draws[1].primitive_start,
+                end: len + 1,
+            }]
+        );
+
+        let mut gpu_data = scene();
+        gpu_data.curve_ranges.truncate(3 * 6 + 3);
+        assert_eq!(gpu_data.decode()[1].errors, [DecodeError::IncompleteDraw]);
+    }
+
+    #[test]
+    fn corrupt_vertices() {
+        // NaN operand of the line
+        let mut gpu_data = scene();
+        let vertex_start = gpu_data.decode()[0].vertex_start as usize;
+        let start = gpu_data.decode()[0].primitive_start;
+        gpu_data.vertices[vertex_start] = 0x7E00_7E00;
+        assert_eq!(
+            gpu_data.decode()[0].errors,
+            [DecodeError::NaN { index: start }]
+        );
+
+        // operands cut off in the second draw
+        let mut gpu_data = scene();
+        let draw = &gpu_data.decode()[1];
+        let (vertex_start, start) = (draw.vertex_start as usize, draw.primitive_start);
+        gpu_data.vertices.truncate(vertex_start + 1);
+        let errors = &gpu_data.decode()[1].errors;
+        assert_eq!(errors.len(), 1);
+        match errors[0] {
+            DecodeError::VertexRange { index } => assert!(index >= start),
+            error => panic!("unexpected error {:?}", error),
+        }
+    }
+}
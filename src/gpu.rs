@@ -1,14 +1,37 @@
-use crate::{Brush, Curve, Rect};
+use crate::path::{rounded_rect_radii, shadow_rect};
+use crate::{
+    glm, Aabb, BlendMode, Brush, Color, Curve, FillRule, GradientStop, Rect, Sampling, SpreadMode,
+    Transform,
+};
 
-const PRIMITIVE_LINE: u32 = 0x1;
-const PRIMITIVE_QUADRATIC: u32 = 0x2;
-const PRIMITIVE_CIRCLE: u32 = 0x3;
-const PRIMITIVE_ARC: u32 = 0x4;
-const PRIMITIVE_RECT: u32 = 0x5;
-const PRIMITIVE_SHADOW_RECT: u32 = 0x6;
+pub(crate) const PRIMITIVE_LINE: u32 = 0x1;
+pub(crate) const PRIMITIVE_QUADRATIC: u32 = 0x2;
+pub(crate) const PRIMITIVE_CIRCLE: u32 = 0x3;
+pub(crate) const PRIMITIVE_ARC: u32 = 0x4;
+pub(crate) const PRIMITIVE_RECT: u32 = 0x5;
+pub(crate) const PRIMITIVE_SHADOW_RECT: u32 = 0x6;
+pub(crate) const PRIMITIVE_ROUNDED_RECT: u32 = 0x7;
 
-const PRIMITIVE_FILL_COLOR: u32 = 0x10;
-const PRIMITIVE_FILL_LINEAR_GRADIENT: u32 = 0x11;
+pub(crate) const PRIMITIVE_FILL_COLOR: u32 = 0x10;
+pub(crate) const PRIMITIVE_FILL_LINEAR_GRADIENT: u32 = 0x11;
+pub(crate) const PRIMITIVE_FILL_RADIAL_GRADIENT: u32 = 0x12;
+pub(crate) const PRIMITIVE_FILL_SWEEP_GRADIENT: u32 = 0x13;
+pub(crate) const PRIMITIVE_FILL_IMAGE: u32 = 0x14;
+
+pub(crate) const PRIMITIVE_CLIP: u32 = 0x20;
+
+pub(crate) const SPREAD_PAD: u32 = 0x0;
+pub(crate) const SPREAD_REPEAT: u32 = 0x1;
+pub(crate) const SPREAD_REFLECT: u32 = 0x2;
+
+pub(crate) const SAMPLING_NEAREST: u32 = 0x0;
+pub(crate) const SAMPLING_LINEAR: u32 = 0x1;
+
+// flags in the upper bits of fill and clip primitives
+pub(crate) const PRIMITIVE_MASK: u32 = 0xFF;
+pub(crate) const FILL_RULE_EVEN_ODD: u32 = 0x100;
+// fill color is composited over white, the neutral source of `BlendOp::Min`
+pub(crate) const FILL_OVER_WHITE: u32 = 0x200;
 
 fn pack_f32(a: f32) -> u32 {
     unsafe { std::mem::transmute(a) }
@@ -25,12 +48,72 @@ fn pack_unorm8x4(x: u8, y: u8, z: u8, w: u8) -> u32 {
     x as u32 | (y as u32) << 8 | (z as u32) << 16 | (w as u32) << 24
 }
 
+// straight to premultiplied alpha, rounded to nearest
+fn pack_color(c: Color) -> u32 {
+    let premultiply = |x: u8| ((x as u32 * c[3] as u32 + 127) / 255) as u8;
+    pack_unorm8x4(
+        premultiply(c[0]),
+        premultiply(c[1]),
+        premultiply(c[2]),
+        c[3],
+    )
+}
+
+fn spread_mode(spread: SpreadMode) -> u32 {
+    match spread {
+        SpreadMode::Pad => SPREAD_PAD,
+        SpreadMode::Repeat => SPREAD_REPEAT,
+        SpreadMode::Reflect => SPREAD_REFLECT,
+    }
+}
+
+fn fill_rule_flags(fill_rule: FillRule) -> u32 {
+    match fill_rule {
+        FillRule::NonZero => 0,
+        FillRule::EvenOdd => FILL_RULE_EVEN_ODD,
+    }
+}
+
+// Scale of the local to curve mapping along one axis, see `Rect::local_to_curve`.
+pub(crate) fn scale_factor(extent_curve: f32, extent_local: f32) -> f32 {
+    if extent_local.abs() > 0.0 {
+        extent_curve / extent_local
+    } else {
+        0.0
+    }
+}
+
+#[derive(Clone)]
+struct ClipPath {
+    curves: Vec<Curve>,
+    fill_rule: FillRule,
+    // intersection with all enclosing clips, `None` if empty
+    bounds: Option<Aabb>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DrawCommand {
+    /// Range of `bbox` vertices (6 per draw) sharing the same blend mode.
+    Draw {
+        start: u32,
+        end: u32,
+        blend_mode: BlendMode,
+    },
+    /// Following draws are rendered into an intermediate target.
+    PushLayer { opacity: f32, blend_mode: BlendMode },
+    /// Composite the current layer onto its parent.
+    PopLayer,
+}
+
 #[derive(Clone)]
 pub struct GpuData {
     pub vertices: Vec<u32>,
     pub primitives: Vec<u32>,
     pub bbox: Vec<f32>,
     pub curve_ranges: Vec<u32>,
+    pub commands: Vec<DrawCommand>,
+    clip_stack: Vec<ClipPath>,
+    blend_mode: BlendMode,
 }
 
 impl GpuData {
@@ -40,12 +123,117 @@ impl GpuData {
             primitives: Vec::new(),
             bbox: Vec::new(),
             curve_ranges: Vec::new(),
+            commands: Vec::new(),
+            clip_stack: Vec::new(),
+            blend_mode: BlendMode::SrcOver,
         }
     }
 
-    pub fn extend(&mut self, path: &[Curve], rect: Rect, brush: &Brush) {
+    /// Clip all following draws to `path` (in local coordinates) until `pop_clip`.
+    ///
+    /// Nested clips intersect with the enclosing ones.
+    pub fn push_clip(&mut self, path: &[Curve], fill_rule: FillRule) {
+        let bounds = Aabb::from_curves(path);
+        let bounds = match self.clip_stack.last() {
+            Some(parent) => parent
+                .bounds
+                .and_then(|parent_bounds| bounds.intersect(&parent_bounds)),
+            None => Some(bounds),
+        };
+
+        self.clip_stack.push(ClipPath {
+            curves: path.to_vec(),
+            fill_rule,
+            bounds,
+        });
+    }
+
+    pub fn pop_clip(&mut self) {
+        self.clip_stack.pop();
+    }
+
+    /// Blend mode of all following draws, `SrcOver` by default.
+    pub fn set_blend_mode(&mut self, blend_mode: BlendMode) {
+        self.blend_mode = blend_mode;
+    }
+
+    /// Render all following draws into a layer until `pop_layer`.
+    ///
+    /// The layer is composited once with `opacity`, overlapping draws inside
+    /// the layer don't blend with each other's faded colors.
+    pub fn push_layer(&mut self, opacity: f32, blend_mode: BlendMode) {
+        self.commands.push(DrawCommand::PushLayer {
+            opacity: opacity.max(0.0).min(1.0),
+            blend_mode,
+        });
+    }
+
+    pub fn pop_layer(&mut self) {
+        self.commands.push(DrawCommand::PopLayer);
+    }
+
+    /// Draw commands with unbalanced layers fixed up: surplus pops are
+    /// dropped and open layers are closed at the end.
+    pub fn balanced_commands(&self) -> Vec<DrawCommand> {
+        let mut depth = 0;
+        let mut commands = Vec::with_capacity(self.commands.len());
+        for command in &self.commands {
+            match *command {
+                DrawCommand::PushLayer { .. } => depth += 1,
+                DrawCommand::PopLayer if depth == 0 => continue,
+                DrawCommand::PopLayer => depth -= 1,
+                DrawCommand::Draw { .. } => (),
+            }
+            commands.push(command.clone());
+        }
+        commands.extend((0..depth).map(|_| DrawCommand::PopLayer));
+        commands
+    }
+
+    pub fn extend(&mut self, path: &[Curve], rect: Rect, brush: &Brush, fill_rule: FillRule) {
+        // gradients without stops paint nothing, as in SVG, the stop count is packed into 16 bits
+        match *brush {
+            Brush::LinearGradient { ref stops, .. }
+            | Brush::RadialGradient { ref stops, .. }
+            | Brush::SweepGradient { ref stops, .. }
+                if stops.is_empty() || stops.len() > 0xFFFF =>
+            {
+                return
+            }
+            // images collapsed onto a line cover nothing
+            Brush::Image { ref transform, .. } if transform.inverse().is_none() => return,
+            _ => (),
+        }
+
+        // cull against the clip bounds
+        let rect = match self.clip_stack.last() {
+            Some(clip) => {
+                let p0 = rect.offset_local;
+                let p1 = rect.offset_local + rect.extent_local;
+                let local = Aabb {
+                    min: glm::min2(&p0, &p1),
+                    max: glm::max2(&p0, &p1),
+                };
+                match clip.bounds.and_then(|bounds| local.intersect(&bounds)) {
+                    Some(aabb) => {
+                        let offset_curve = rect.local_to_curve(aabb.min);
+                        Rect {
+                            offset_local: aabb.min,
+                            extent_local: aabb.max - aabb.min,
+                            offset_curve,
+                            extent_curve: rect.local_to_curve(aabb.max) - offset_curve,
+                        }
+                    }
+                    // outside of the clip or the clip is empty
+                    None => return,
+                }
+            }
+            None => rect,
+        };
+
         let primitive_start = self.primitives.len() as u32;
         let vertex_start = self.vertices.len() as u32;
+        let bbox_start = self.bbox.len() as u32 / 4;
 
         let min_local = rect.offset_local;
         let max_local = rect.offset_local + rect.extent_local;
@@ -80,70 +268,144 @@ impl GpuData {
             max_curve.y,
         ]);
 
-        for curve in path {
-            match curve {
-                Curve::Line { p0, p1 } => {
-                    self.vertices
-                        .extend(&[pack_f16x2(p0.x, p0.y), pack_f16x2(p1.x, p1.y)]);
-                    self.primitives.push(PRIMITIVE_LINE);
-                }
-                Curve::Quad { p0, p1, p2 } => {
-                    self.vertices.extend(&[
-                        pack_f16x2(p0.x, p0.y),
-                        pack_f16x2(p1.x, p1.y),
-                        pack_f16x2(p2.x, p2.y),
-                    ]);
-                    self.primitives.push(PRIMITIVE_QUADRATIC);
-                }
-                Curve::Circle { center, radius } => {
-                    self.vertices
-                        .extend(&[pack_f16x2(center.x, center.y), pack_f32(*radius)]);
-                    self.primitives.push(PRIMITIVE_CIRCLE);
-                }
-                Curve::Arc { center, p0, p1 } => {
-                    self.vertices.extend(&[
-                        pack_f16x2(center.x, center.y),
-                        pack_f16x2(p0.x - center.x, p0.y - center.y),
-                        pack_f16x2(p1.x - center.x, p1.y - center.y),
-                    ]);
-                    self.primitives.push(PRIMITIVE_ARC);
-                }
-                Curve::Rect { p0, p1 } => {
-                    self.vertices
-                        .extend(&[pack_f16x2(p0.x, p0.y), pack_f16x2(p1.x, p1.y)]);
-                    self.primitives.push(PRIMITIVE_RECT);
-                }
+        // clip paths are moved into the curve space of the draw
+        let local_to_curve = Transform::translate(-rect.offset_local)
+            .then(&Transform::scale(
+                scale_factor(rect.extent_curve.x, rect.extent_local.x),
+                scale_factor(rect.extent_curve.y, rect.extent_local.y),
+            ))
+            .then(&Transform::translate(rect.offset_curve));
+        let clips = self
+            .clip_stack
+            .iter()
+            .map(|clip| {
+                (
+                    Curve::transform_path(&clip.curves, &local_to_curve),
+                    fill_rule_flags(clip.fill_rule),
+                )
+            })
+            .collect::<Vec<_>>();
+        for (curves, flags) in clips {
+            for curve in &curves {
+                self.push_curve(curve);
             }
+            self.primitives.push(PRIMITIVE_CLIP | flags);
+        }
+
+        for curve in path {
+            self.push_curve(curve);
+        }
+
+        let mut fill_flags = fill_rule_flags(fill_rule);
+        if self.blend_mode.over_white() {
+            fill_flags |= FILL_OVER_WHITE;
         }
 
         match *brush {
             Brush::Color(ref c) => {
-                self.primitives.push(PRIMITIVE_FILL_COLOR);
-                self.vertices.push(pack_unorm8x4(c[0], c[1], c[2], c[3]));
+                self.primitives.push(PRIMITIVE_FILL_COLOR | fill_flags);
+                self.vertices.push(pack_color(*c));
             }
             Brush::LinearGradient {
-                ref stop0,
-                ref stop1,
+                start,
+                end,
+                ref stops,
+                spread,
             } => {
-                self.primitives.push(PRIMITIVE_FILL_LINEAR_GRADIENT);
-
+                self.primitives
+                    .push(PRIMITIVE_FILL_LINEAR_GRADIENT | fill_flags);
                 self.vertices
-                    .push(pack_f16x2(stop0.position.x, stop0.position.y));
-                self.vertices.push(pack_unorm8x4(
-                    stop0.color[0],
-                    stop0.color[1],
-                    stop0.color[2],
-                    stop0.color[3],
-                ));
+                    .extend(&[pack_f16x2(start.x, start.y), pack_f16x2(end.x, end.y)]);
+                self.push_gradient_stops(stops, spread);
+            }
+            Brush::RadialGradient {
+                radius, ref stops, ..
+            } if radius <= 0.0 => {
+                // degenerate circle, painted with the last stop as in SVG
+                let color = stops.last().map_or([0; 4], |stop| stop.color);
+                self.primitives.push(PRIMITIVE_FILL_COLOR | fill_flags);
+                self.vertices.push(pack_color(color));
+            }
+            Brush::RadialGradient {
+                center,
+                radius,
+                focal,
+                ref stops,
+                spread,
+            } => {
+                self.primitives
+                    .push(PRIMITIVE_FILL_RADIAL_GRADIENT | fill_flags);
 
-                self.vertices
-                    .push(pack_f16x2(stop1.position.x, stop1.position.y));
-                self.vertices.push(pack_unorm8x4(
-                    stop1.color[0],
-                    stop1.color[1],
-                    stop1.color[2],
-                    stop1.color[3],
-                ));
+                // keep focal point strictly inside the circle
+                let offset = focal - center;
+                let max_offset = 0.99 * radius;
+                let focal = if glm::length(&offset) > max_offset {
+                    center + max_offset * glm::normalize(&offset)
+                } else {
+                    focal
+                };
+
+                self.vertices.extend(&[
+                    pack_f16x2(center.x, center.y),
+                    pack_f16x2(focal.x, focal.y),
+                    pack_f32(radius),
+                ]);
+                self.push_gradient_stops(stops, spread);
+            }
+            Brush::SweepGradient {
+                center,
+                start_angle,
+                end_angle,
+                ref stops,
+            } => {
+                // counter-clockwise sweep in (0, 2pi], larger spans are reduced by full turns
+                let two_pi = 2.0 * std::f32::consts::PI;
+                let sweep = two_pi - (start_angle - end_angle).rem_euclid(two_pi);
+
+                if end_angle == start_angle || sweep <= 0.0 || !sweep.is_finite() {
+                    // no sweep, painted with the last stop
+                    let color = stops.last().map_or([0; 4], |stop| stop.color);
+                    self.primitives.push(PRIMITIVE_FILL_COLOR | fill_flags);
+                    self.vertices.push(pack_color(color));
+                } else {
+                    self.primitives
+                        .push(PRIMITIVE_FILL_SWEEP_GRADIENT | fill_flags);
+                    self.vertices.extend(&[
+                        pack_f16x2(center.x, center.y),
+                        pack_f32(start_angle),
+                        pack_f32(start_angle + sweep),
+                    ]);
+                    self.push_gradient_stops(stops, SpreadMode::Pad);
+                }
+            }
+            Brush::Image {
+                image_id,
+                ref transform,
+                sampling,
+                extend,
+            } => {
+                self.primitives.push(PRIMITIVE_FILL_IMAGE | fill_flags);
+
+                let sampling = match sampling {
+                    Sampling::Nearest => SAMPLING_NEAREST,
+                    Sampling::Linear => SAMPLING_LINEAR,
+                };
+
+                // shader maps world positions back into image texel space, checked above
+                let inverse = transform.inverse().unwrap();
+                let m = inverse.matrix;
+                let t = inverse.translation;
+
+                self.vertices.extend(&[
+                    image_id,
+                    sampling | spread_mode(extend) << 8,
+                    pack_f32(m[(0, 0)]),
+                    pack_f32(m[(1, 0)]),
+                    pack_f32(m[(0, 1)]),
+                    pack_f32(m[(1, 1)]),
+                    pack_f32(t.x),
+                    pack_f32(t.y),
+                ]);
             }
         }
 
@@ -169,5 +431,127 @@ impl GpuData {
             primitive_start,
             primitive_end,
         ]);
+
+        // batch with the previous draw if the pipeline state matches
+        let bbox_end = self.bbox.len() as u32 / 4;
+        let blend_mode = self.blend_mode;
+        match self.commands.last_mut() {
+            Some(DrawCommand::Draw {
+                end,
+                blend_mode: mode,
+                ..
+            }) if *end == bbox_start && *mode == blend_mode => *end = bbox_end,
+            _ => self.commands.push(DrawCommand::Draw {
+                start: bbox_start,
+                end: bbox_end,
+                blend_mode,
+            }),
+        }
+    }
+
+    // Stop table: header (16 bit number of stops, spread mode) followed by (offset, color) pairs.
+    // Colors are premultiplied, gradients interpolate in premultiplied space.
+    // Offsets are clamped to `[0, 1]` and made non-decreasing.
+    fn push_gradient_stops(&mut self, stops: &[GradientStop], spread: SpreadMode) {
+        debug_assert!(!stops.is_empty() && stops.len() <= 0xFFFF);
+
+        let spread = spread_mode(spread);
+        self.vertices.push(stops.len() as u32 | spread << 16);
+
+        let mut last_offset = 0.0f32;
+        for stop in stops {
+            let offset = stop.offset.max(last_offset).min(1.0);
+            self.vertices
+                .extend(&[pack_f32(offset), pack_color(stop.color)]);
+            last_offset = offset;
+        }
+    }
+
+    fn push_curve(&mut self, curve: &Curve) {
+        match curve {
+            Curve::Line { p0, p1 } => {
+                self.vertices
+                    .extend(&[pack_f16x2(p0.x, p0.y), pack_f16x2(p1.x, p1.y)]);
+                self.primitives.push(PRIMITIVE_LINE);
+            }
+            Curve::Quad { p0, p1, p2 } => {
+                self.vertices.extend(&[
+                    pack_f16x2(p0.x, p0.y),
+                    pack_f16x2(p1.x, p1.y),
+                    pack_f16x2(p2.x, p2.y),
+                ]);
+                self.primitives.push(PRIMITIVE_QUADRATIC);
+            }
+            Curve::Cubic { .. } | Curve::Ellipse { .. } => {
+                for quad in curve.monotonize() {
+                    self.push_curve(&quad);
+                }
+            }
+            Curve::Circle { center, radius } => {
+                self.vertices
+                    .extend(&[pack_f16x2(center.x, center.y), pack_f32(*radius)]);
+                self.primitives.push(PRIMITIVE_CIRCLE);
+            }
+            Curve::Arc { center, p0, p1 } => {
+                self.vertices.extend(&[
+                    pack_f16x2(center.x, center.y),
+                    pack_f16x2(p0.x - center.x, p0.y - center.y),
+                    pack_f16x2(p1.x - center.x, p1.y - center.y),
+                ]);
+                self.primitives.push(PRIMITIVE_ARC);
+            }
+            Curve::Rect { p0, p1 } => {
+                self.vertices
+                    .extend(&[pack_f16x2(p0.x, p0.y), pack_f16x2(p1.x, p1.y)]);
+                self.primitives.push(PRIMITIVE_RECT);
+            }
+            Curve::RoundedRect { p0, p1, radii } => {
+                // shader expects radii in the order lower-left, lower-right, upper-right, upper-left
+                let r = rounded_rect_radii(*p0, *p1, *radii);
+                let flip_x = p0.x > p1.x;
+                let flip_y = p0.y > p1.y;
+                let corners = [
+                    (flip_x, flip_y),
+                    (!flip_x, flip_y),
+                    (!flip_x, !flip_y),
+                    (flip_x, !flip_y),
+                ];
+
+                let mut sorted = [0.0; 4];
+                for (i, &corner) in corners.iter().enumerate() {
+                    let slot = match corner {
+                        (false, false) => 0,
+                        (true, false) => 1,
+                        (true, true) => 2,
+                        (false, true) => 3,
+                    };
+                    sorted[slot] = r[i];
+                }
+
+                self.vertices.extend(&[
+                    pack_f16x2(p0.x, p0.y),
+                    pack_f16x2(p1.x, p1.y),
+                    pack_f16x2(sorted[0], sorted[1]),
+                    pack_f16x2(sorted[2], sorted[3]),
+                ]);
+                self.primitives.push(PRIMITIVE_ROUNDED_RECT);
+            }
+            Curve::ShadowRect {
+                p0,
+                p1,
+                radius,
+                sigma,
+                spread,
+            } => {
+                let (p0, p1, radius) = shadow_rect(*p0, *p1, *radius, *spread);
+                self.vertices.extend(&[
+                    pack_f16x2(p0.x, p0.y),
+                    pack_f16x2(p1.x, p1.y),
+                    pack_f32(sigma.max(1e-3)),
+                    pack_f32(radius),
+                ]);
+                self.primitives.push(PRIMITIVE_SHADOW_RECT);
+            }
+        }
     }
 }
@@ -2,18 +2,29 @@ use nalgebra_glm as glm;
 
 mod app;
 mod app_wgpu;
+mod atlas;
+mod blend;
 mod brush;
+pub mod cpu;
+mod decode;
 mod gpu;
 mod path;
+mod svg;
 mod text;
+mod transform;
 mod viewport;
 
 pub use crate::app::*;
 pub use crate::app_wgpu::*;
+pub use crate::atlas::*;
+pub use crate::blend::*;
 pub use crate::brush::*;
+pub use crate::decode::*;
 pub use crate::gpu::*;
 pub use crate::path::*;
+pub use crate::svg::*;
 pub use crate::text::*;
+pub use crate::transform::*;
 pub use crate::viewport::*;
 
 pub type Offset = glm::Vec2;
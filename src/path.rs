@@ -1,15 +1,73 @@
-use crate::glm;
+use crate::{glm, Transform};
 
 pub type Segment = Vec<Curve>;
 
+// max. distance between a curve and its quadratic approximation
+pub(crate) const QUAD_TOLERANCE: f32 = 0.05;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub enum CurveCap {
     Butt,
     Round,
+    Square,
 }
 
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub enum CurveJoin {
     Bevel,
     Round,
+    /// Sharp corner, falls back to `Bevel` if the miter limit is exceeded.
+    Miter,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum FillRule {
+    NonZero,
+    EvenOdd,
+}
+
+impl FillRule {
+    pub fn is_inside(&self, winding: i32) -> bool {
+        match *self {
+            FillRule::NonZero => winding != 0,
+            FillRule::EvenOdd => winding % 2 != 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DashPattern {
+    /// Alternating dash and gap lengths, odd-length lists are repeated (as in SVG).
+    pub lengths: Vec<f32>,
+    /// Distance into the pattern at which each subpath starts.
+    pub offset: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StrokeStyle {
+    pub width: f32,
+    pub start_cap: CurveCap,
+    pub end_cap: CurveCap,
+    pub join: CurveJoin,
+    /// Max. ratio of miter length to stroke width (`stroke-miterlimit` in SVG).
+    pub miter_limit: f32,
+    /// Each dash is capped with `start_cap` and `end_cap`, zero-length dashes are
+    /// only drawn if one of the caps isn't butt.
+    pub dash: Option<DashPattern>,
+}
+
+impl StrokeStyle {
+    /// Stroke style with SVG defaults: butt caps and miter joins with a limit of 4.
+    pub fn new(width: f32) -> Self {
+        StrokeStyle {
+            width,
+            start_cap: CurveCap::Butt,
+            end_cap: CurveCap::Butt,
+            join: CurveJoin::Miter,
+            miter_limit: 4.0,
+            dash: None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -23,6 +81,12 @@ pub enum Curve {
         p1: glm::Vec2,
         p2: glm::Vec2,
     },
+    Cubic {
+        p0: glm::Vec2,
+        p1: glm::Vec2,
+        p2: glm::Vec2,
+        p3: glm::Vec2,
+    },
     Circle {
         center: glm::Vec2,
         radius: f32,
@@ -32,10 +96,224 @@ pub enum Curve {
         p0: glm::Vec2,
         p1: glm::Vec2,
     },
+    /// Ellipse with semi-axes `radii`, rotated counter-clockwise by `rotation` (radians).
+    Ellipse {
+        center: glm::Vec2,
+        radii: glm::Vec2,
+        rotation: f32,
+    },
     Rect {
         p0: glm::Vec2,
         p1: glm::Vec2,
     },
+    /// Rectangle with circular corners. `radii` start at the `p0` corner and follow the
+    /// corners `p0`, `(p1.x, p0.y)`, `p1`, `(p0.x, p1.y)`.
+    RoundedRect {
+        p0: glm::Vec2,
+        p1: glm::Vec2,
+        radii: [f32; 4],
+    },
+    /// Gaussian blurred rectangle (`box-shadow` in CSS) with standard deviation `sigma`.
+    ///
+    /// The rectangle is grown by `spread` before blurring, corners are rounded by `radius`.
+    ShadowRect {
+        p0: glm::Vec2,
+        p1: glm::Vec2,
+        radius: f32,
+        sigma: f32,
+        spread: f32,
+    },
+}
+
+// Corner radii scaled down uniformly if adjacent corners overlap (as in CSS).
+pub(crate) fn rounded_rect_radii(p0: glm::Vec2, p1: glm::Vec2, radii: [f32; 4]) -> [f32; 4] {
+    let r = [
+        radii[0].max(0.0),
+        radii[1].max(0.0),
+        radii[2].max(0.0),
+        radii[3].max(0.0),
+    ];
+    let w = (p1.x - p0.x).abs();
+    let h = (p1.y - p0.y).abs();
+
+    let mut scale = 1.0f32;
+    for &(side, a, b) in &[
+        (w, r[0], r[1]),
+        (h, r[1], r[2]),
+        (w, r[2], r[3]),
+        (h, r[3], r[0]),
+    ] {
+        if a + b > side {
+            scale = scale.min(side / (a + b));
+        }
+    }
+
+    [scale * r[0], scale * r[1], scale * r[2], scale * r[3]]
+}
+
+// Lines and arcs along the border of a rounded rect, same orientation as the coverage.
+fn rounded_rect_outline(p0: glm::Vec2, p1: glm::Vec2, radii: [f32; 4]) -> Vec<Curve> {
+    if p0.x == p1.x || p0.y == p1.y {
+        return Vec::new();
+    }
+
+    let r = rounded_rect_radii(p0, p1, radii);
+    let corners = [
+        (p0, r[0]),
+        (glm::vec2(p0.x, p1.y), r[3]),
+        (p1, r[2]),
+        (glm::vec2(p1.x, p0.y), r[1]),
+    ];
+
+    let mut curves = Vec::new();
+    let mut arcs = Vec::new();
+    for i in 0..4 {
+        let (prev, _) = corners[(i + 3) % 4];
+        let (corner, radius) = corners[i];
+        let (next, _) = corners[(i + 1) % 4];
+
+        let d_in = glm::normalize(&(corner - prev));
+        let d_out = glm::normalize(&(next - corner));
+        arcs.push((
+            corner - radius * d_in,
+            corner + radius * d_out,
+            corner - radius * d_in + radius * d_out,
+        ));
+    }
+
+    for i in 0..4 {
+        let (start, end, center) = arcs[i];
+        if start != end {
+            curves.extend(
+                Curve::Arc {
+                    center,
+                    p0: start,
+                    p1: end,
+                }
+                .monotonize(),
+            );
+        }
+
+        let (next, _, _) = arcs[(i + 1) % 4];
+        if end != next {
+            curves.push(Curve::Line { p0: end, p1: next });
+        }
+    }
+
+    curves
+}
+
+// Shadow rectangle grown by `spread` and the matching corner radius, keeps the orientation.
+pub(crate) fn shadow_rect(
+    p0: glm::Vec2,
+    p1: glm::Vec2,
+    radius: f32,
+    spread: f32,
+) -> (glm::Vec2, glm::Vec2, f32) {
+    let dir = glm::sign(&(p1 - p0));
+    let p0 = p0 - spread * dir;
+    let p1 = p1 + spread * dir;
+
+    // corners stay sharp if there is no radius (CSS behavior)
+    let extent = p1 - p0;
+    let max_radius = 0.5 * extent.x.abs().min(extent.y.abs());
+    let radius = if radius > 0.0 {
+        (radius + spread).max(0.0).min(max_radius)
+    } else {
+        0.0
+    };
+
+    (p0, p1, radius)
+}
+
+// Start angle, signed sweep angle and radii of an arc.
+// Arcs always take the shorter way from `p0` to `p1`.
+fn arc_params(center: glm::Vec2, p0: glm::Vec2, p1: glm::Vec2) -> (f32, f32, f32, f32) {
+    let d0 = p0 - center;
+    let d1 = p1 - center;
+
+    let a0 = d0.y.atan2(d0.x);
+    let mut sweep = d1.y.atan2(d1.x) - a0;
+    if sweep > std::f32::consts::PI {
+        sweep -= 2.0 * std::f32::consts::PI;
+    } else if sweep <= -std::f32::consts::PI {
+        sweep += 2.0 * std::f32::consts::PI;
+    }
+
+    (a0, sweep, glm::length(&d0), glm::length(&d1))
+}
+
+// Quadratic approximation of a circular arc, each piece spans at most 45 degree.
+fn arc_to_quads(
+    center: glm::Vec2,
+    a0: f32,
+    sweep: f32,
+    r0: f32,
+    r1: f32,
+    tolerance: f32,
+) -> Vec<Curve> {
+    // deviation of a single piece is about `r * step^4 / 128`
+    let max_step = (128.0 * tolerance / r0.max(r1))
+        .powf(0.25)
+        .min(std::f32::consts::FRAC_PI_4);
+    let n = (sweep.abs() / max_step).ceil().max(1.0) as usize;
+    let step = sweep / n as f32;
+
+    let point = |t: f32, r: f32| {
+        let a = a0 + t * sweep;
+        center + r * glm::vec2(a.cos(), a.sin())
+    };
+
+    (0..n)
+        .map(|i| {
+            let t0 = i as f32 / n as f32;
+            let t1 = (i + 1) as f32 / n as f32;
+            let ra = (1.0 - t0) * r0 + t0 * r1;
+            let rb = (1.0 - t1) * r0 + t1 * r1;
+            Curve::Quad {
+                p0: point(t0, ra),
+                p1: point(0.5 * (t0 + t1), 0.5 * (ra + rb) / (0.5 * step).cos()),
+                p2: point(t1, rb),
+            }
+        })
+        .collect()
+}
+
+// Quadratic approximation of an ellipse, clockwise like circles.
+fn ellipse_to_quads(
+    center: glm::Vec2,
+    radii: glm::Vec2,
+    rotation: f32,
+    tolerance: f32,
+) -> Vec<Curve> {
+    let (rx, ry) = (radii.x.abs(), radii.y.abs());
+    let max_radius = rx.max(ry);
+    if max_radius == 0.0 {
+        return Vec::new();
+    }
+
+    // unit circle scaled afterwards, quads are invariant under affine maps
+    let to_world = Transform::scale(rx, ry)
+        .then(&Transform::rotate(rotation))
+        .then(&Transform::translate(center));
+    arc_to_quads(
+        glm::vec2(0.0, 0.0),
+        0.0,
+        -2.0 * std::f32::consts::PI,
+        1.0,
+        1.0,
+        tolerance / max_radius,
+    )
+    .iter()
+    .map(|quad| match *quad {
+        Curve::Quad { p0, p1, p2 } => Curve::Quad {
+            p0: to_world.apply(p0),
+            p1: to_world.apply(p1),
+            p2: to_world.apply(p2),
+        },
+        _ => unreachable!(),
+    })
+    .collect()
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -59,6 +337,33 @@ impl Aabb {
         }
     }
 
+    /// Overlapping area, `None` if the boxes are disjoint.
+    pub fn intersect(&self, other: &Aabb) -> Option<Aabb> {
+        let min = glm::vec2(self.min.x.max(other.min.x), self.min.y.max(other.min.y));
+        let max = glm::vec2(self.max.x.min(other.max.x), self.max.y.min(other.max.y));
+        if min.x <= max.x && min.y <= max.y {
+            Some(Aabb { min, max })
+        } else {
+            None
+        }
+    }
+
+    fn include(&self, p: glm::Vec2) -> Aabb {
+        Aabb {
+            min: glm::Vec2::new(self.min.x.min(p.x), self.min.y.min(p.y)),
+            max: glm::Vec2::new(self.max.x.max(p.x), self.max.y.max(p.y)),
+        }
+    }
+
+    /// Bounding box of the transformed box.
+    pub fn transform(&self, transform: &Transform) -> Aabb {
+        let p = transform.apply(self.min);
+        Aabb { min: p, max: p }
+            .include(transform.apply(glm::vec2(self.max.x, self.min.y)))
+            .include(transform.apply(glm::vec2(self.min.x, self.max.y)))
+            .include(transform.apply(self.max))
+    }
+
     pub fn from_curves(curves: &[Curve]) -> Self {
         curves.iter().fold(
             Aabb {
@@ -84,24 +389,99 @@ impl Curve {
                 max: glm::Vec2::new(p0.x.max(p1.x), p0.y.max(p1.y)),
             },
             Curve::Quad { p0, p1, p2 } => {
-                // bad approx - could be tighter
-                Aabb {
-                    min: glm::Vec2::new(p0.x.min(p1.x).min(p2.x), p0.y.min(p1.y).min(p2.y)),
-                    max: glm::Vec2::new(p0.x.max(p1.x).max(p2.x), p0.y.max(p1.y).max(p2.y)),
+                // endpoints and interior extrema
+                let mut aabb = Curve::Line { p0, p1: p2 }.aabb();
+                let denom = p0 - 2.0 * p1 + p2;
+                for axis in 0..2 {
+                    if denom[axis] != 0.0 {
+                        let t = (p0[axis] - p1[axis]) / denom[axis];
+                        if t > 0.0 && t < 1.0 {
+                            aabb = aabb.include(self.eval(t));
+                        }
+                    }
                 }
+                aabb
+            }
+            Curve::Cubic { p0, p1, p2, p3 } => {
+                // endpoints and interior extrema (roots of the derivative)
+                let mut aabb = Curve::Line { p0, p1: p3 }.aabb();
+                let a = -p0 + 3.0 * p1 - 3.0 * p2 + p3;
+                let b = 2.0 * (p0 - 2.0 * p1 + p2);
+                let c = p1 - p0;
+                for axis in 0..2 {
+                    let (a, b, c) = (a[axis], b[axis], c[axis]);
+                    let roots = if a.abs() < 1e-6 {
+                        if b != 0.0 {
+                            [Some(-c / b), None]
+                        } else {
+                            [None, None]
+                        }
+                    } else {
+                        let dscr = b * b - 4.0 * a * c;
+                        if dscr >= 0.0 {
+                            let sq = dscr.sqrt();
+                            [Some((-b + sq) / (2.0 * a)), Some((-b - sq) / (2.0 * a))]
+                        } else {
+                            [None, None]
+                        }
+                    };
+                    for t in roots.iter().filter_map(|t| *t) {
+                        if t > 0.0 && t < 1.0 {
+                            aabb = aabb.include(self.eval(t));
+                        }
+                    }
+                }
+                aabb
             }
             Curve::Circle { center, radius } => Aabb {
                 min: center - glm::vec2(radius, radius),
                 max: center + glm::vec2(radius, radius),
             },
-            Curve::Arc { p0, p1, .. } => Aabb {
-                min: glm::Vec2::new(p0.x.min(p1.x), p0.y.min(p1.y)),
-                max: glm::Vec2::new(p0.x.max(p1.x), p0.y.max(p1.y)),
-            },
+            Curve::Ellipse {
+                center,
+                radii,
+                rotation,
+            } => {
+                let (sin, cos) = rotation.sin_cos();
+                let extent = glm::vec2(
+                    (radii.x * radii.x * cos * cos + radii.y * radii.y * sin * sin).sqrt(),
+                    (radii.x * radii.x * sin * sin + radii.y * radii.y * cos * cos).sqrt(),
+                );
+                Aabb {
+                    min: center - extent,
+                    max: center + extent,
+                }
+            }
+            Curve::Arc { p0, p1, .. } => {
+                // monotonic pieces end at the extrema
+                self.monotonize()
+                    .iter()
+                    .fold(Curve::Line { p0, p1 }.aabb(), |aabb, curve| match *curve {
+                        Curve::Arc { p1, .. } => aabb.include(p1),
+                        _ => unreachable!(),
+                    })
+            }
             Curve::Rect { p0, p1 } => Aabb {
                 min: glm::Vec2::new(p0.x.min(p1.x), p0.y.min(p1.y)),
                 max: glm::Vec2::new(p0.x.max(p1.x), p0.y.max(p1.y)),
             },
+            Curve::RoundedRect { p0, p1, .. } => Curve::Rect { p0, p1 }.aabb(),
+            Curve::ShadowRect {
+                p0,
+                p1,
+                radius,
+                sigma,
+                spread,
+            } => {
+                // coverage is negligible beyond 3 sigma
+                let (p0, p1, _) = shadow_rect(p0, p1, radius, spread);
+                let blur = glm::vec2(3.0 * sigma, 3.0 * sigma);
+                let aabb = Curve::Rect { p0, p1 }.aabb();
+                Aabb {
+                    min: aabb.min - blur,
+                    max: aabb.max + blur,
+                }
+            }
         }
     }
 
@@ -111,12 +491,317 @@ impl Curve {
             Curve::Quad { p0, p1, p2 } => {
                 (1.0 - t) * (1.0 - t) * p0 + 2.0 * t * (1.0 - t) * p1 + t * t * p2
             }
-            Curve::Circle { .. } => todo!(),
-            Curve::Arc { .. } => todo!(),
-            Curve::Rect { .. } => todo!(),
+            Curve::Cubic { p0, p1, p2, p3 } => {
+                let mt = 1.0 - t;
+                mt * mt * mt * p0 + 3.0 * mt * mt * t * p1 + 3.0 * mt * t * t * p2 + t * t * t * p3
+            }
+            Curve::Circle { center, radius } => {
+                // clockwise like the coverage, see `to_quads`
+                let a = -2.0 * std::f32::consts::PI * t;
+                center + radius * glm::vec2(a.cos(), a.sin())
+            }
+            Curve::Arc { center, p0, p1 } => {
+                let (a0, sweep, r0, r1) = arc_params(center, p0, p1);
+                let a = a0 + t * sweep;
+                center + ((1.0 - t) * r0 + t * r1) * glm::vec2(a.cos(), a.sin())
+            }
+            Curve::Ellipse {
+                center,
+                radii,
+                rotation,
+            } => {
+                let a = -2.0 * std::f32::consts::PI * t;
+                let p = glm::vec2(radii.x * a.cos(), radii.y * a.sin());
+                center + Transform::rotate(rotation).apply_vector(p)
+            }
+            Curve::Rect { p0, p1 } => {
+                // along the border starting at `p0`, same orientation as the rect coverage
+                let corners = [p0, glm::vec2(p0.x, p1.y), p1, glm::vec2(p1.x, p0.y), p0];
+                let i = ((4.0 * t).floor().max(0.0) as usize).min(3);
+                let f = 4.0 * t - i as f32;
+                (1.0 - f) * corners[i] + f * corners[i + 1]
+            }
+            Curve::ShadowRect {
+                p0,
+                p1,
+                radius,
+                spread,
+                ..
+            } => {
+                let (p0, p1, _) = shadow_rect(p0, p1, radius, spread);
+                Curve::Rect { p0, p1 }.eval(t)
+            }
+            Curve::RoundedRect { p0, p1, radii } => {
+                // uniform parameterization over the outline pieces
+                let outline = rounded_rect_outline(p0, p1, radii);
+                if outline.is_empty() {
+                    return p0;
+                }
+                let n = outline.len();
+                let i = ((n as f32 * t).floor().max(0.0) as usize).min(n - 1);
+                outline[i].eval(n as f32 * t - i as f32)
+            }
+        }
+    }
+
+    /// Derivative of the curve with respect to `t`.
+    pub fn tangent(&self, t: f32) -> glm::Vec2 {
+        match *self {
+            Curve::Line { p0, p1 } => p1 - p0,
+            Curve::Quad { p0, p1, p2 } => 2.0 * ((1.0 - t) * (p1 - p0) + t * (p2 - p1)),
+            Curve::Cubic { p0, p1, p2, p3 } => {
+                let mt = 1.0 - t;
+                3.0 * mt * mt * (p1 - p0) + 6.0 * mt * t * (p2 - p1) + 3.0 * t * t * (p3 - p2)
+            }
+            Curve::Circle { radius, .. } => {
+                let a = -2.0 * std::f32::consts::PI * t;
+                -2.0 * std::f32::consts::PI * radius * glm::vec2(-a.sin(), a.cos())
+            }
+            Curve::Arc { center, p0, p1 } => {
+                let (a0, sweep, r0, r1) = arc_params(center, p0, p1);
+                let a = a0 + t * sweep;
+                let r = (1.0 - t) * r0 + t * r1;
+                (r1 - r0) * glm::vec2(a.cos(), a.sin()) + r * sweep * glm::vec2(-a.sin(), a.cos())
+            }
+            Curve::Ellipse {
+                radii, rotation, ..
+            } => {
+                let a = -2.0 * std::f32::consts::PI * t;
+                let v = glm::vec2(-radii.x * a.sin(), radii.y * a.cos());
+                -2.0 * std::f32::consts::PI * Transform::rotate(rotation).apply_vector(v)
+            }
+            Curve::Rect { p0, p1 } => {
+                let corners = [p0, glm::vec2(p0.x, p1.y), p1, glm::vec2(p1.x, p0.y), p0];
+                let i = ((4.0 * t).floor().max(0.0) as usize).min(3);
+                4.0 * (corners[i + 1] - corners[i])
+            }
+            Curve::ShadowRect {
+                p0,
+                p1,
+                radius,
+                spread,
+                ..
+            } => {
+                let (p0, p1, _) = shadow_rect(p0, p1, radius, spread);
+                Curve::Rect { p0, p1 }.tangent(t)
+            }
+            Curve::RoundedRect { p0, p1, radii } => {
+                let outline = rounded_rect_outline(p0, p1, radii);
+                if outline.is_empty() {
+                    return glm::vec2(0.0, 0.0);
+                }
+                let n = outline.len();
+                let i = ((n as f32 * t).floor().max(0.0) as usize).min(n - 1);
+                n as f32 * outline[i].tangent(n as f32 * t - i as f32)
+            }
+        }
+    }
+
+    /// Approximate cubics, arcs, circles and ellipses by a sequence of quadratic curves.
+    ///
+    /// The curve is split uniformly such that each quadratic piece deviates at most
+    /// `tolerance` from the original curve. Circles and ellipses are traversed clockwise,
+    /// matching the orientation of their coverage. Other curves are returned unchanged.
+    pub fn to_quads(&self, tolerance: f32) -> Vec<Curve> {
+        match *self {
+            Curve::Cubic { p0, p1, p2, p3 } => {
+                let dd = p3 - 3.0 * p2 + 3.0 * p1 - p0;
+                let err = 3.0f32.sqrt() / 36.0 * glm::length(&dd);
+                let n = (err / tolerance).cbrt().ceil().max(1.0) as usize;
+
+                let dt = 1.0 / n as f32;
+                (0..n)
+                    .map(|i| {
+                        let t0 = i as f32 / n as f32;
+                        let t1 = (i + 1) as f32 / n as f32;
+                        let q0 = self.eval(t0);
+                        let q3 = self.eval(t1);
+                        let q1 = q0 + dt / 3.0 * self.tangent(t0);
+                        let q2 = q3 - dt / 3.0 * self.tangent(t1);
+                        Curve::Quad {
+                            p0: q0,
+                            p1: (3.0 * (q1 + q2) - q0 - q3) / 4.0,
+                            p2: q3,
+                        }
+                    })
+                    .collect()
+            }
+            Curve::Circle { center, radius } => arc_to_quads(
+                center,
+                0.0,
+                -2.0 * std::f32::consts::PI,
+                radius,
+                radius,
+                tolerance,
+            ),
+            Curve::Ellipse {
+                center,
+                radii,
+                rotation,
+            } => ellipse_to_quads(center, radii, rotation, tolerance),
+            Curve::Arc { center, p0, p1 } => {
+                let (a0, sweep, r0, r1) = arc_params(center, p0, p1);
+                let mut quads = arc_to_quads(center, a0, sweep, r0, r1, tolerance);
+
+                // keep endpoints exact
+                if let Some(Curve::Quad { p0: start, .. }) = quads.first_mut() {
+                    *start = p0;
+                }
+                if let Some(Curve::Quad { p2: end, .. }) = quads.last_mut() {
+                    *end = p1;
+                }
+                quads
+            }
+            _ => vec![*self],
         }
     }
 
+    /// Apply an affine transformation.
+    ///
+    /// Circles, ellipses and arcs are converted to quadratic curves if the transformation
+    /// doesn't preserve circles, rectangles are converted to lines if they don't stay
+    /// axis-aligned. Rounded rectangles additionally require a uniform scale, shadows
+    /// lose their blur otherwise. Reflections turn circles into arcs and ellipses into
+    /// quadratic curves as both are always clockwise. Resulting quads and arcs are monotonic.
+    pub fn transform(&self, transform: &Transform) -> Vec<Curve> {
+        let t = |p: glm::Vec2| transform.apply(p);
+
+        match *self {
+            Curve::Line { p0, p1 } => vec![Curve::Line {
+                p0: t(p0),
+                p1: t(p1),
+            }],
+            Curve::Quad { p0, p1, p2 } => Curve::Quad {
+                p0: t(p0),
+                p1: t(p1),
+                p2: t(p2),
+            }
+            .monotonize(),
+            Curve::Cubic { p0, p1, p2, p3 } => vec![Curve::Cubic {
+                p0: t(p0),
+                p1: t(p1),
+                p2: t(p2),
+                p3: t(p3),
+            }],
+            Curve::Circle { center, radius } => match transform.uniform_scale() {
+                Some(scale) if transform.matrix.determinant() > 0.0 => vec![Curve::Circle {
+                    center: t(center),
+                    radius: scale * radius,
+                }],
+                Some(_) => {
+                    // reflections reverse the orientation, circles are always clockwise
+                    let corners = [
+                        center + glm::vec2(-radius, 0.0),
+                        center + glm::vec2(0.0, radius),
+                        center + glm::vec2(radius, 0.0),
+                        center + glm::vec2(0.0, -radius),
+                    ];
+                    let arcs = (0..4)
+                        .map(|i| Curve::Arc {
+                            center,
+                            p0: corners[i],
+                            p1: corners[(i + 1) % 4],
+                        })
+                        .collect::<Vec<_>>();
+                    Curve::transform_path(&arcs, transform)
+                }
+                None => Curve::transform_path(&self.to_quads(QUAD_TOLERANCE), transform),
+            },
+            Curve::Ellipse {
+                center,
+                radii,
+                rotation,
+            } => match transform.uniform_scale() {
+                Some(scale) if transform.matrix.determinant() > 0.0 => {
+                    // major axis direction
+                    let axis = transform.apply_vector(glm::vec2(rotation.cos(), rotation.sin()));
+                    vec![Curve::Ellipse {
+                        center: t(center),
+                        radii: scale * radii,
+                        rotation: axis.y.atan2(axis.x),
+                    }]
+                }
+                _ => Curve::transform_path(&self.to_quads(QUAD_TOLERANCE), transform),
+            },
+            Curve::Arc { center, p0, p1 } => match transform.uniform_scale() {
+                Some(_) => Curve::Arc {
+                    center: t(center),
+                    p0: t(p0),
+                    p1: t(p1),
+                }
+                .monotonize(),
+                None => Curve::transform_path(&self.to_quads(QUAD_TOLERANCE), transform),
+            },
+            Curve::Rect { p0, p1 } => {
+                if transform.is_axis_aligned() {
+                    vec![Curve::Rect {
+                        p0: t(p0),
+                        p1: t(p1),
+                    }]
+                } else {
+                    // same orientation as the rect coverage
+                    let corners = [
+                        t(p0),
+                        t(glm::vec2(p0.x, p1.y)),
+                        t(p1),
+                        t(glm::vec2(p1.x, p0.y)),
+                    ];
+                    (0..4)
+                        .map(|i| Curve::Line {
+                            p0: corners[i],
+                            p1: corners[(i + 1) % 4],
+                        })
+                        .collect()
+                }
+            }
+            Curve::RoundedRect { p0, p1, radii } => {
+                match (transform.is_axis_aligned(), transform.uniform_scale()) {
+                    (true, Some(scale)) => vec![Curve::RoundedRect {
+                        p0: t(p0),
+                        p1: t(p1),
+                        radii: [
+                            scale * radii[0],
+                            scale * radii[1],
+                            scale * radii[2],
+                            scale * radii[3],
+                        ],
+                    }],
+                    _ => Curve::transform_path(&rounded_rect_outline(p0, p1, radii), transform),
+                }
+            }
+            Curve::ShadowRect {
+                p0,
+                p1,
+                radius,
+                sigma,
+                spread,
+            } => {
+                if transform.is_axis_aligned() {
+                    // non-uniform scaling is approximated by the average scale
+                    let scale = transform.matrix.determinant().abs().sqrt();
+                    vec![Curve::ShadowRect {
+                        p0: t(p0),
+                        p1: t(p1),
+                        radius: scale * radius,
+                        sigma: scale * sigma,
+                        spread: scale * spread,
+                    }]
+                } else {
+                    let (p0, p1, _) = shadow_rect(p0, p1, radius, spread);
+                    Curve::Rect { p0, p1 }.transform(transform)
+                }
+            }
+        }
+    }
+
+    pub fn transform_path(curves: &[Curve], transform: &Transform) -> Vec<Curve> {
+        curves
+            .iter()
+            .map(|curve| curve.transform(transform))
+            .flatten()
+            .collect()
+    }
+
     pub fn monotonize(&self) -> Vec<Curve> {
         match *self {
             Curve::Line { .. } => vec![*self],
@@ -158,12 +843,176 @@ impl Curve {
                     (None, None) => vec![*self],
                 }
             }
+            Curve::Cubic { .. } | Curve::Ellipse { .. } => self
+                .to_quads(QUAD_TOLERANCE)
+                .iter()
+                .map(|quad| quad.monotonize())
+                .flatten()
+                .collect(),
             Curve::Circle { .. } => vec![*self],
-            Curve::Rect { .. } => vec![*self],
-            Curve::Arc { .. } => vec![*self], // todo
+            Curve::Rect { .. } | Curve::RoundedRect { .. } | Curve::ShadowRect { .. } => {
+                vec![*self]
+            }
+            Curve::Arc { center, p0, p1 } => {
+                // split at quadrant boundaries
+                const EPSILON: f32 = 1e-4;
+                let quadrant = std::f32::consts::FRAC_PI_2;
+                let axis = [
+                    glm::vec2(1.0, 0.0),
+                    glm::vec2(0.0, 1.0),
+                    glm::vec2(-1.0, 0.0),
+                    glm::vec2(0.0, -1.0),
+                ];
+
+                let (a0, sweep, r0, r1) = arc_params(center, p0, p1);
+                let a1 = a0 + sweep;
+
+                let mut curves = Vec::new();
+                let mut start = p0;
+                let mut split = |k: i32| {
+                    let t = (k as f32 * quadrant - a0) / sweep;
+                    let r = (1.0 - t) * r0 + t * r1;
+                    let p = center + r * axis[k.rem_euclid(4) as usize];
+                    curves.push(Curve::Arc {
+                        center,
+                        p0: start,
+                        p1: p,
+                    });
+                    start = p;
+                };
+
+                if sweep > 0.0 {
+                    let mut k = (a0 / quadrant).floor() as i32 + 1;
+                    while k as f32 * quadrant < a1 - EPSILON {
+                        split(k);
+                        k += 1;
+                    }
+                } else {
+                    let mut k = (a0 / quadrant).ceil() as i32 - 1;
+                    while k as f32 * quadrant > a1 + EPSILON {
+                        split(k);
+                        k -= 1;
+                    }
+                }
+
+                curves.push(Curve::Arc {
+                    center,
+                    p0: start,
+                    p1,
+                });
+                curves
+            }
         }
     }
 
+    /// Winding number of the curves around `p`, matching the coverage orientation of the shader.
+    pub fn winding(curves: &[Curve], p: glm::Vec2) -> i32 {
+        let mut winding = 0;
+
+        for curve in curves {
+            match *curve {
+                Curve::Circle { center, radius } => {
+                    if glm::distance(&center, &p) < radius {
+                        winding += 1;
+                    }
+                }
+                Curve::Rect { p0, p1 } => {
+                    let side = |a: f32, b: f32, x: f32| {
+                        if a <= x && x < b {
+                            1
+                        } else if b <= x && x < a {
+                            -1
+                        } else {
+                            0
+                        }
+                    };
+                    winding += side(p0.x, p1.x, p.x) * side(p0.y, p1.y, p.y);
+                }
+                Curve::RoundedRect { p0, p1, radii } => {
+                    winding += Curve::winding(&rounded_rect_outline(p0, p1, radii), p);
+                }
+                Curve::ShadowRect {
+                    p0,
+                    p1,
+                    radius,
+                    spread,
+                    ..
+                } => {
+                    // area of the unblurred shadow
+                    let (p0, p1, _) = shadow_rect(p0, p1, radius, spread);
+                    winding += Curve::winding(&[Curve::Rect { p0, p1 }], p);
+                }
+                _ => {
+                    for piece in curve.monotonize() {
+                        let (a, b) = (piece.eval(0.0), piece.eval(1.0));
+                        if a.x.min(b.x) > p.x || p.x >= a.x.max(b.x) {
+                            continue;
+                        }
+
+                        // bisect the ray intersection, x is monotonic
+                        let dir = if b.x > a.x { 1 } else { -1 };
+                        let (mut t0, mut t1) = (0.0f32, 1.0f32);
+                        for _ in 0..24 {
+                            let t = 0.5 * (t0 + t1);
+                            if (piece.eval(t).x < p.x) == (dir > 0) {
+                                t0 = t;
+                            } else {
+                                t1 = t;
+                            }
+                        }
+
+                        if piece.eval(0.5 * (t0 + t1)).y > p.y {
+                            winding += dir;
+                        }
+                    }
+                }
+            }
+        }
+
+        winding
+    }
+
+    /// Border of a rounded rectangle, `width` extends inwards from the outer edge.
+    ///
+    /// The inner rectangle has the opposite orientation, fill with `FillRule::NonZero`.
+    pub fn rounded_rect_border(
+        p0: glm::Vec2,
+        p1: glm::Vec2,
+        radii: [f32; 4],
+        width: f32,
+    ) -> Vec<Curve> {
+        let radii = rounded_rect_radii(p0, p1, radii);
+        let mut curves = vec![Curve::RoundedRect { p0, p1, radii }];
+
+        let extent = p1 - p0;
+        if 2.0 * width >= extent.x.abs().min(extent.y.abs()) {
+            return curves;
+        }
+
+        let dir = glm::sign(&extent);
+        let q0 = p0 + width * dir;
+        let q1 = p1 - width * dir;
+        let inner = |r: f32| (r - width).max(0.0);
+
+        // swap x to flip the orientation, corners are reordered accordingly
+        curves.push(Curve::RoundedRect {
+            p0: glm::vec2(q1.x, q0.y),
+            p1: glm::vec2(q0.x, q1.y),
+            radii: [
+                inner(radii[1]),
+                inner(radii[0]),
+                inner(radii[3]),
+                inner(radii[2]),
+            ],
+        });
+        curves
+    }
+
+    /// Test if `p` is inside the area covered by the curves.
+    pub fn hit_test(curves: &[Curve], p: glm::Vec2, fill_rule: FillRule) -> bool {
+        fill_rule.is_inside(Curve::winding(curves, p))
+    }
+
     pub fn monotize_path(curves: &[Curve]) -> Vec<Curve> {
         curves
             .iter()
@@ -178,43 +1027,167 @@ pub enum PathElement {
     MoveTo(glm::Vec2),
     LineTo(glm::Vec2),
     QuadTo(glm::Vec2, glm::Vec2),
+    CubicTo(glm::Vec2, glm::Vec2, glm::Vec2),
     ArcTo(glm::Vec2, glm::Vec2),
     Close,
 }
 
 pub struct PathBuilder {
     elements: Vec<PathElement>,
+    // start of the current subpath and end point of the last element
+    start: glm::Vec2,
+    current: glm::Vec2,
 }
 
 impl PathBuilder {
     pub fn new() -> Self {
         PathBuilder {
             elements: Vec::new(),
+            start: glm::vec2(0.0, 0.0),
+            current: glm::vec2(0.0, 0.0),
+        }
+    }
+
+    fn push(&mut self, element: PathElement) {
+        match element {
+            PathElement::MoveTo(p) => {
+                self.start = p;
+                self.current = p;
+            }
+            PathElement::LineTo(p)
+            | PathElement::QuadTo(_, p)
+            | PathElement::CubicTo(_, _, p)
+            | PathElement::ArcTo(_, p) => self.current = p,
+            PathElement::Close => self.current = self.start,
         }
+        self.elements.push(element);
     }
 
     pub fn move_to(mut self, p: glm::Vec2) -> Self {
-        self.elements.push(PathElement::MoveTo(p));
+        self.push(PathElement::MoveTo(p));
         self
     }
 
     pub fn line_to(mut self, p: glm::Vec2) -> Self {
-        self.elements.push(PathElement::LineTo(p));
+        self.push(PathElement::LineTo(p));
         self
     }
 
     pub fn quad_to(mut self, p1: glm::Vec2, p2: glm::Vec2) -> Self {
-        self.elements.push(PathElement::QuadTo(p1, p2));
+        self.push(PathElement::QuadTo(p1, p2));
         self
     }
 
+    pub fn cubic_to(mut self, p1: glm::Vec2, p2: glm::Vec2, p3: glm::Vec2) -> Self {
+        self.push(PathElement::CubicTo(p1, p2, p3));
+        self
+    }
+
+    /// Circular arc around `center`, taking the shorter way to `p1`.
     pub fn arc_to(mut self, center: glm::Vec2, p1: glm::Vec2) -> Self {
-        self.elements.push(PathElement::ArcTo(center, p1));
+        self.push(PathElement::ArcTo(center, p1));
         self
     }
 
+    /// Elliptical arc to `end` in SVG endpoint parameterization, `x_rotation` in radians.
+    ///
+    /// Radii are scaled up if no ellipse passes through both endpoints. The arc is
+    /// approximated by cubic curves of at most 90 degree.
+    /// See SVG 1.1 implementation notes F.6.5 for the conversion to center parameterization.
+    pub fn elliptical_arc_to(
+        self,
+        radii: glm::Vec2,
+        x_rotation: f32,
+        large_arc: bool,
+        sweep: bool,
+        end: glm::Vec2,
+    ) -> Self {
+        let p0 = self.current;
+        let p1 = end;
+        if p0 == p1 {
+            return self;
+        }
+
+        let mut rx = radii.x.abs();
+        let mut ry = radii.y.abs();
+        if rx == 0.0 || ry == 0.0 {
+            return self.line_to(p1);
+        }
+
+        let (sin_phi, cos_phi) = x_rotation.sin_cos();
+        let rotate =
+            |v: glm::Vec2| glm::vec2(cos_phi * v.x - sin_phi * v.y, sin_phi * v.x + cos_phi * v.y);
+
+        let d = 0.5 * (p0 - p1);
+        let x1 = cos_phi * d.x + sin_phi * d.y;
+        let y1 = -sin_phi * d.x + cos_phi * d.y;
+
+        let lambda = (x1 * x1) / (rx * rx) + (y1 * y1) / (ry * ry);
+        if lambda > 1.0 {
+            rx *= lambda.sqrt();
+            ry *= lambda.sqrt();
+        }
+
+        let num = rx * rx * ry * ry - rx * rx * y1 * y1 - ry * ry * x1 * x1;
+        let den = rx * rx * y1 * y1 + ry * ry * x1 * x1;
+        let sign = if large_arc == sweep { -1.0 } else { 1.0 };
+        let coef = sign * (num / den).max(0.0).sqrt();
+        let cx = coef * rx * y1 / ry;
+        let cy = -coef * ry * x1 / rx;
+
+        let center = rotate(glm::vec2(cx, cy)) + 0.5 * (p0 + p1);
+
+        let theta0 = ((y1 - cy) / ry).atan2((x1 - cx) / rx);
+        let theta1 = ((-y1 - cy) / ry).atan2((-x1 - cx) / rx);
+        let mut delta = theta1 - theta0;
+        if sweep && delta < 0.0 {
+            delta += 2.0 * std::f32::consts::PI;
+        } else if !sweep && delta > 0.0 {
+            delta -= 2.0 * std::f32::consts::PI;
+        }
+
+        // split into pieces of at most 90 degree
+        let num_segments = (delta.abs() / std::f32::consts::FRAC_PI_2).ceil().max(1.0) as usize;
+        let step = delta / num_segments as f32;
+        let k = 4.0 / 3.0 * (0.25 * step).tan();
+
+        let to_world = |v: glm::Vec2| center + rotate(glm::vec2(rx * v.x, ry * v.y));
+
+        let mut builder = self;
+        for i in 0..num_segments {
+            let a0 = theta0 + i as f32 * step;
+            let a1 = a0 + step;
+            let (s0, c0) = a0.sin_cos();
+            let (s1, c1) = a1.sin_cos();
+
+            let c1p = glm::vec2(c0 - k * s0, s0 + k * c0);
+            let c2p = glm::vec2(c1 + k * s1, s1 - k * c1);
+            let end = if i + 1 == num_segments {
+                p1
+            } else {
+                to_world(glm::vec2(c1, s1))
+            };
+
+            builder = builder.cubic_to(to_world(c1p), to_world(c2p), end);
+        }
+
+        builder
+    }
+
+    /// Closed ellipse as a new subpath, counter-clockwise starting at the end of the major axis.
+    pub fn ellipse(self, center: glm::Vec2, radii: glm::Vec2, rotation: f32) -> Self {
+        let axis = glm::vec2(rotation.cos(), rotation.sin());
+        let start = center + radii.x * axis;
+        let mid = center - radii.x * axis;
+
+        self.move_to(start)
+            .elliptical_arc_to(radii, rotation, false, true, mid)
+            .elliptical_arc_to(radii, rotation, false, true, start)
+            .close()
+    }
+
     pub fn close(mut self) -> Self {
-        self.elements.push(PathElement::Close);
+        self.push(PathElement::Close);
         self
     }
 
@@ -227,39 +1200,257 @@ impl PathBuilder {
         for element in self.elements {
             match element {
                 PathElement::LineTo(p) => {
-                    builder.elements.push(PathElement::LineTo(p));
+                    builder.push(PathElement::LineTo(p));
                     p0 = p;
                 }
                 PathElement::MoveTo(p) => {
-                    builder.elements.push(PathElement::MoveTo(p));
+                    builder.push(PathElement::MoveTo(p));
                     p0 = p;
                     initial = p;
                 }
                 PathElement::Close => {
-                    builder.elements.push(PathElement::Close);
+                    builder.push(PathElement::Close);
                     p0 = initial;
                 }
                 PathElement::QuadTo(p1, p2) => {
                     let curves = Curve::Quad { p0, p1, p2 }.monotonize();
                     for curve in curves {
                         if let Curve::Quad { p1, p2, .. } = curve {
-                            builder.elements.push(PathElement::QuadTo(p1, p2));
+                            builder.push(PathElement::QuadTo(p1, p2));
                             p0 = p2;
                         } else {
                             unreachable!()
                         }
                     }
                 }
-                PathElement::ArcTo(_, _) => todo!(), // validation only
+                PathElement::CubicTo(p1, p2, p3) => {
+                    let curves = Curve::Cubic { p0, p1, p2, p3 }.monotonize();
+                    for curve in curves {
+                        if let Curve::Quad { p1, p2, .. } = curve {
+                            builder.push(PathElement::QuadTo(p1, p2));
+                        } else {
+                            unreachable!()
+                        }
+                    }
+                    p0 = p3;
+                }
+                PathElement::ArcTo(center, p1) => {
+                    let curves = Curve::Arc { center, p0, p1 }.monotonize();
+                    for curve in curves {
+                        if let Curve::Arc { p1, .. } = curve {
+                            builder.push(PathElement::ArcTo(center, p1));
+                        } else {
+                            unreachable!()
+                        }
+                    }
+                    p0 = p1;
+                }
             }
         }
 
         builder
     }
 
-    pub fn stroke(self, distance: f32, caps: (CurveCap, CurveJoin, CurveCap)) -> Vec<Curve> {
+    // Replace cubics by monotonic quadratic curves and split arcs at quadrant boundaries,
+    // other elements are kept as is.
+    fn split_elements(&self) -> Vec<PathElement> {
+        let mut elements = Vec::with_capacity(self.elements.len());
+
+        let mut p0 = glm::vec2(0.0f32, 0.0f32);
+        let mut initial = p0;
+
+        for element in &self.elements {
+            match *element {
+                PathElement::MoveTo(p) => {
+                    p0 = p;
+                    initial = p;
+                }
+                PathElement::LineTo(p) | PathElement::QuadTo(_, p) => {
+                    p0 = p;
+                }
+                PathElement::Close => {
+                    p0 = initial;
+                }
+                PathElement::CubicTo(p1, p2, p3) => {
+                    for curve in (Curve::Cubic { p0, p1, p2, p3 }).monotonize() {
+                        if let Curve::Quad { p1, p2, .. } = curve {
+                            elements.push(PathElement::QuadTo(p1, p2));
+                        } else {
+                            unreachable!()
+                        }
+                    }
+                    p0 = p3;
+                    continue;
+                }
+                PathElement::ArcTo(center, p1) => {
+                    for curve in (Curve::Arc { center, p0, p1 }).monotonize() {
+                        if let Curve::Arc { p1, .. } = curve {
+                            elements.push(PathElement::ArcTo(center, p1));
+                        } else {
+                            unreachable!()
+                        }
+                    }
+                    p0 = p1;
+                    continue;
+                }
+            }
+            elements.push(*element);
+        }
+
+        elements
+    }
+
+    // Split lines and quads into dashes, each dash starts a new subpath.
+    // Zero-length dashes are returned separately as dots (position and direction).
+    fn dash(
+        elements: &[PathElement],
+        pattern: &DashPattern,
+    ) -> (Vec<PathElement>, Vec<(glm::Vec2, glm::Vec2)>) {
+        const NUM_SAMPLES: usize = 32;
+
+        let mut lengths = pattern.lengths.clone();
+        if lengths.len() % 2 == 1 {
+            lengths.extend_from_slice(&pattern.lengths);
+        }
+
+        let total: f32 = lengths.iter().sum();
+        if total <= 0.0 || lengths.iter().any(|l| *l < 0.0) {
+            return (elements.to_vec(), Vec::new());
+        }
+
+        let phase = pattern.offset.rem_euclid(total);
+        let reset = || {
+            let mut phase = phase;
+            let mut index = 0;
+            while phase > lengths[index] {
+                phase -= lengths[index];
+                index = (index + 1) % lengths.len();
+            }
+            (index, lengths[index] - phase)
+        };
+
+        let mut dashes = Vec::new();
+        let mut dots = Vec::new();
+
+        let (mut index, mut remaining) = reset();
+        let mut open = false;
+
+        let mut p0 = glm::vec2(0.0f32, 0.0f32);
+        let mut initial = p0;
+
+        for element in elements {
+            let (curve, end) = match *element {
+                PathElement::MoveTo(p) => {
+                    let (i, r) = reset();
+                    index = i;
+                    remaining = r;
+                    open = false;
+                    p0 = p;
+                    initial = p;
+                    continue;
+                }
+                PathElement::LineTo(p1) => (Curve::Line { p0, p1 }, p1),
+                PathElement::QuadTo(p1, p2) => (Curve::Quad { p0, p1, p2 }, p2),
+                PathElement::ArcTo(center, p1) => (Curve::Arc { center, p0, p1 }, p1),
+                PathElement::Close => (Curve::Line { p0, p1: initial }, initial),
+                PathElement::CubicTo(..) => unreachable!(),
+            };
+
+            // cumulative arc length at uniform parameter samples
+            let mut table = vec![0.0f32; NUM_SAMPLES + 1];
+            for i in 0..NUM_SAMPLES {
+                let a = curve.eval(i as f32 / NUM_SAMPLES as f32);
+                let b = curve.eval((i + 1) as f32 / NUM_SAMPLES as f32);
+                table[i + 1] = table[i] + glm::distance(&a, &b);
+            }
+            let length = table[NUM_SAMPLES];
+
+            // zero-length segments have no direction for dashes or dots
+            if length <= 0.0 {
+                p0 = end;
+                continue;
+            }
+
+            let param = |s: f32| {
+                let i = table
+                    .iter()
+                    .rposition(|l| *l <= s)
+                    .unwrap_or(0)
+                    .min(NUM_SAMPLES - 1);
+                let span = table[i + 1] - table[i];
+                let f = if span > 0.0 {
+                    ((s - table[i]) / span).min(1.0)
+                } else {
+                    0.0
+                };
+                (i as f32 + f) / NUM_SAMPLES as f32
+            };
+
+            let mut pos = 0.0;
+            loop {
+                let on = index % 2 == 0;
+
+                if remaining <= 0.0 {
+                    if on && lengths[index] == 0.0 {
+                        let t = param(pos);
+                        let tangent = curve.tangent(t);
+                        if glm::length(&tangent) > 0.0 {
+                            dots.push((curve.eval(t), glm::normalize(&tangent)));
+                        }
+                    }
+                    if on {
+                        open = false;
+                    }
+                    index = (index + 1) % lengths.len();
+                    remaining = lengths[index];
+                    continue;
+                }
+
+                if pos >= length {
+                    break;
+                }
+
+                let t0 = param(pos);
+                if remaining < length - pos {
+                    pos += remaining;
+                    remaining = 0.0;
+                } else {
+                    remaining -= length - pos;
+                    pos = length;
+                }
+                let (t1, p1) = if pos < length {
+                    (param(pos), curve.eval(param(pos)))
+                } else {
+                    (1.0, end)
+                };
+
+                if on {
+                    if !open {
+                        dashes.push(PathElement::MoveTo(curve.eval(t0)));
+                        open = true;
+                    }
+                    dashes.push(match curve {
+                        Curve::Quad { .. } => PathElement::QuadTo(
+                            curve.eval(t0) + 0.5 * (t1 - t0) * curve.tangent(t0),
+                            p1,
+                        ),
+                        Curve::Arc { center, .. } => PathElement::ArcTo(center, p1),
+                        _ => PathElement::LineTo(p1),
+                    });
+                }
+            }
+
+            p0 = end;
+        }
+
+        (dashes, dots)
+    }
+
+    pub fn stroke(self, style: &StrokeStyle) -> Vec<Curve> {
         let mut curves = Vec::new();
 
+        let distance = 0.5 * style.width;
+
         let mut p0 = glm::vec2(0.0f32, 0.0f32);
         let mut n0 = glm::vec2(0.0f32, 0.0f32);
         let mut begin = None;
@@ -307,6 +1498,29 @@ impl PathBuilder {
                 });
             };
 
+        let add_miter_join =
+            |p: glm::Vec2, n0: glm::Vec2, n1: glm::Vec2, curves: &mut Vec<Curve>| {
+                let cos = glm::dot(&n0, &n1);
+                let miter = (n0 + n1) / (1.0 + cos);
+                let outer = n0.x * n1.y - n0.y * n1.x < 0.0;
+
+                if outer && cos > -1.0 && glm::length(&miter) <= style.miter_limit {
+                    curves.push(Curve::Line {
+                        p0: p + distance * n0,
+                        p1: p + distance * miter,
+                    });
+                    curves.push(Curve::Line {
+                        p0: p + distance * miter,
+                        p1: p + distance * n1,
+                    });
+                } else {
+                    curves.push(Curve::Line {
+                        p0: p + distance * n0,
+                        p1: p + distance * n1,
+                    });
+                }
+            };
+
         let add_round_caps = |p: glm::Vec2, n: glm::Vec2, curves: &mut Vec<Curve>| {
             let pattern = [
                 glm::vec2(-1.0, 0.0),
@@ -338,7 +1552,81 @@ impl PathBuilder {
             });
         };
 
-        for element in &self.elements {
+        let add_square_caps = |p: glm::Vec2, n: glm::Vec2, curves: &mut Vec<Curve>| {
+            let dir = glm::vec2(n.y, -n.x);
+
+            curves.push(Curve::Line {
+                p0: p + distance * n,
+                p1: p + distance * (n + dir),
+            });
+            curves.push(Curve::Line {
+                p0: p + distance * (n + dir),
+                p1: p + distance * (dir - n),
+            });
+            curves.push(Curve::Line {
+                p0: p + distance * (dir - n),
+                p1: p - distance * n,
+            });
+        };
+
+        let add_join = |p: glm::Vec2, n0: glm::Vec2, n1: glm::Vec2, curves: &mut Vec<Curve>| {
+            match style.join {
+                CurveJoin::Round => {
+                    add_round_join(p, n0, n1, curves);
+                    add_round_join(p, -n1, -n0, curves);
+                }
+                CurveJoin::Miter => {
+                    add_miter_join(p, n0, n1, curves);
+                    add_miter_join(p, -n1, -n0, curves);
+                }
+                CurveJoin::Bevel => {
+                    curves.push(Curve::Line {
+                        p0: p + distance * n0,
+                        p1: p + distance * n1,
+                    });
+                    curves.push(Curve::Line {
+                        p0: p - distance * n1,
+                        p1: p - distance * n0,
+                    });
+                }
+            }
+        };
+
+        let add_caps = |p_start: glm::Vec2,
+                        n_start: glm::Vec2,
+                        p_end: glm::Vec2,
+                        n_end: glm::Vec2,
+                        curves: &mut Vec<Curve>| {
+            match style.start_cap {
+                CurveCap::Round => add_round_caps(p_start, -n_start, curves),
+                CurveCap::Square => add_square_caps(p_start, -n_start, curves),
+                CurveCap::Butt => {
+                    curves.push(Curve::Line {
+                        p0: p_start - distance * n_start,
+                        p1: p_start + distance * n_start,
+                    });
+                }
+            }
+
+            match style.end_cap {
+                CurveCap::Round => add_round_caps(p_end, n_end, curves),
+                CurveCap::Square => add_square_caps(p_end, n_end, curves),
+                CurveCap::Butt => {
+                    curves.push(Curve::Line {
+                        p0: p_end + distance * n_end,
+                        p1: p_end - distance * n_end,
+                    });
+                }
+            }
+        };
+
+        let elements = self.split_elements();
+        let (elements, dots) = match style.dash {
+            Some(ref pattern) => PathBuilder::dash(&elements, pattern),
+            None => (elements, Vec::new()),
+        };
+
+        for element in &elements {
             match *element {
                 PathElement::LineTo(p1) => {
                     let dir = glm::normalize(&(p1 - p0));
@@ -355,22 +1643,7 @@ impl PathBuilder {
                     });
 
                     match begin {
-                        Some(_) => match caps.1 {
-                            CurveJoin::Round => {
-                                add_round_join(p0, n0, n, &mut curves);
-                                add_round_join(p0, -n, -n0, &mut curves);
-                            }
-                            CurveJoin::Bevel => {
-                                curves.push(dbg!(Curve::Line {
-                                    p0: p0 + distance * n0,
-                                    p1: p0 + distance * n
-                                }));
-                                curves.push(dbg!(Curve::Line {
-                                    p0: p0 - distance * n,
-                                    p1: p0 - distance * n0
-                                }));
-                            }
-                        },
+                        Some(_) => add_join(p0, n0, n, &mut curves),
                         None => {
                             begin = Some((p0, n));
                         }
@@ -399,22 +1672,7 @@ impl PathBuilder {
                     });
 
                     match begin {
-                        Some(_) => match caps.1 {
-                            CurveJoin::Round => {
-                                add_round_join(p0, n0, normal0, &mut curves);
-                                add_round_join(p0, -normal0, -n0, &mut curves);
-                            }
-                            CurveJoin::Bevel => {
-                                curves.push(dbg!(Curve::Line {
-                                    p0: p0 + distance * n0,
-                                    p1: p0 + distance * normal0
-                                }));
-                                curves.push(dbg!(Curve::Line {
-                                    p0: p0 - distance * normal0,
-                                    p1: p0 - distance * n0
-                                }));
-                            }
-                        },
+                        Some(_) => add_join(p0, n0, normal0, &mut curves),
                         None => {
                             begin = Some((p0, normal0));
                         }
@@ -426,25 +1684,7 @@ impl PathBuilder {
                 PathElement::MoveTo(p) => {
                     if let Some((p, n)) = begin.take() {
                         // close off
-                        match caps.0 {
-                            CurveCap::Round => add_round_caps(p, -n, &mut curves),
-                            CurveCap::Butt => {
-                                curves.push(Curve::Line {
-                                    p0: p - distance * n,
-                                    p1: p + distance * n,
-                                });
-                            }
-                        }
-
-                        match caps.2 {
-                            CurveCap::Round => add_round_caps(p0, n0, &mut curves),
-                            CurveCap::Butt => {
-                                curves.push(Curve::Line {
-                                    p0: p0 + distance * n0,
-                                    p1: p0 - distance * n0,
-                                });
-                            }
-                        }
+                        add_caps(p, n, p0, n0, &mut curves);
                     }
 
                     p0 = p;
@@ -454,33 +1694,43 @@ impl PathBuilder {
                         let dir = glm::normalize(&(p1 - p0));
                         let n = glm::vec2(-dir.y, dir.x);
 
-                        curves.push(Curve::Line {
-                            p0: p0 + distance * n0,
-                            p1: p0 + distance * n,
-                        }); // connection to prior
+                        match style.join {
+                            CurveJoin::Miter => {
+                                add_miter_join(p0, n0, n, &mut curves); // connection to prior
+                                add_miter_join(p1, n, n1, &mut curves); // connection to initial
+                                add_miter_join(p0, -n, -n0, &mut curves); // connection to prior
+                                add_miter_join(p1, -n1, -n, &mut curves); // connection to initial
+                            }
+                            CurveJoin::Bevel | CurveJoin::Round => {
+                                curves.push(Curve::Line {
+                                    p0: p0 + distance * n0,
+                                    p1: p0 + distance * n,
+                                }); // connection to prior
+                                curves.push(Curve::Line {
+                                    p0: p1 + distance * n,
+                                    p1: p1 + distance * n1,
+                                }); // connection to initial
+                                curves.push(Curve::Line {
+                                    p0: p0 - distance * n,
+                                    p1: p0 - distance * n0,
+                                }); // connection to prior
+                                curves.push(Curve::Line {
+                                    p0: p1 - distance * n1,
+                                    p1: p1 - distance * n,
+                                }); // connection to initial
+                            }
+                        }
+
                         curves.push(Curve::Line {
                             p0: p0 + distance * n,
                             p1: p1 + distance * n,
                         }); // extruded
-                        curves.push(Curve::Line {
-                            p0: p1 + distance * n,
-                            p1: p1 + distance * n1,
-                        }); // connection to initial
-
-                        curves.push(Curve::Line {
-                            p0: p0 - distance * n,
-                            p1: p0 - distance * n0,
-                        }); // connection to prior
                         curves.push(Curve::Line {
                             p0: p1 - distance * n,
                             p1: p0 - distance * n,
                         }); // extruded
-                        curves.push(Curve::Line {
-                            p0: p1 - distance * n1,
-                            p1: p1 - distance * n,
-                        }); // connection to initial
 
-                        if let CurveJoin::Round = caps.1 {
+                        if let CurveJoin::Round = style.join {
                             curves.push(Curve::Circle {
                                 center: p0,
                                 radius: distance,
@@ -492,37 +1742,115 @@ impl PathBuilder {
                         }
                     }
                 }
-                PathElement::ArcTo(_, _) => todo!(),
+                PathElement::ArcTo(center, p1) => {
+                    let arc = Curve::Arc { center, p0, p1 };
+                    let d0 = glm::normalize(&arc.tangent(0.0));
+                    let d1 = glm::normalize(&arc.tangent(1.0));
+                    let normal0 = glm::vec2(-d0.y, d0.x);
+                    let normal1 = glm::vec2(-d1.y, d1.x);
+
+                    // extruded arcs, normals are radial
+                    curves.push(Curve::Arc {
+                        center,
+                        p0: p0 + distance * normal0,
+                        p1: p1 + distance * normal1,
+                    });
+                    curves.push(Curve::Arc {
+                        center,
+                        p0: p1 - distance * normal1,
+                        p1: p0 - distance * normal0,
+                    });
+
+                    match begin {
+                        Some(_) => add_join(p0, n0, normal0, &mut curves),
+                        None => {
+                            begin = Some((p0, normal0));
+                        }
+                    }
+
+                    p0 = p1;
+                    n0 = normal1;
+                }
+                PathElement::CubicTo(..) => unreachable!(),
             }
         }
 
         // remaining path - same as a move to
         if let Some((p, n)) = begin.take() {
             // close off
-            match caps.0 {
-                CurveCap::Round => add_round_caps(p, -n, &mut curves),
-                CurveCap::Butt => {
-                    curves.push(Curve::Line {
-                        p0: p - distance * n,
-                        p1: p + distance * n,
-                    });
-                }
-            }
+            add_caps(p, n, p0, n0, &mut curves);
+        }
 
-            match caps.2 {
-                CurveCap::Round => add_round_caps(p0, n0, &mut curves),
-                CurveCap::Butt => {
-                    curves.push(Curve::Line {
-                        p0: p0 + distance * n0,
-                        p1: p0 - distance * n0,
-                    });
-                }
-            }
+        // zero-length dashes, butt caps on both ends would have no area
+        let dots = if style.start_cap == CurveCap::Butt && style.end_cap == CurveCap::Butt {
+            Vec::new()
+        } else {
+            dots
+        };
+        for (p, dir) in dots {
+            let n = glm::vec2(-dir.y, dir.x);
+            add_caps(p, n, p, n, &mut curves);
         }
 
         curves
     }
 
+    /// Apply an affine transformation to all elements.
+    ///
+    /// Arcs are converted to quadratic curves if the transformation doesn't preserve circles.
+    pub fn transform(&self, transform: &Transform) -> PathBuilder {
+        let mut builder = PathBuilder::new();
+        let t = |p: glm::Vec2| transform.apply(p);
+        let similarity = transform.uniform_scale().is_some();
+
+        let mut p0 = glm::vec2(0.0f32, 0.0f32);
+        let mut initial = p0;
+
+        for element in &self.elements {
+            let element = match *element {
+                PathElement::MoveTo(p) => {
+                    initial = p;
+                    p0 = p;
+                    PathElement::MoveTo(t(p))
+                }
+                PathElement::LineTo(p) => {
+                    p0 = p;
+                    PathElement::LineTo(t(p))
+                }
+                PathElement::QuadTo(p1, p2) => {
+                    p0 = p2;
+                    PathElement::QuadTo(t(p1), t(p2))
+                }
+                PathElement::CubicTo(p1, p2, p3) => {
+                    p0 = p3;
+                    PathElement::CubicTo(t(p1), t(p2), t(p3))
+                }
+                PathElement::ArcTo(center, p1) if similarity => {
+                    p0 = p1;
+                    PathElement::ArcTo(t(center), t(p1))
+                }
+                PathElement::ArcTo(center, p1) => {
+                    for curve in (Curve::Arc { center, p0, p1 }).to_quads(QUAD_TOLERANCE) {
+                        if let Curve::Quad { p1, p2, .. } = curve {
+                            builder.push(PathElement::QuadTo(t(p1), t(p2)));
+                        } else {
+                            unreachable!()
+                        }
+                    }
+                    p0 = p1;
+                    continue;
+                }
+                PathElement::Close => {
+                    p0 = initial;
+                    PathElement::Close
+                }
+            };
+            builder.push(element);
+        }
+
+        builder
+    }
+
     pub fn fill(self) -> PathSplitter {
         let mut splitter = PathSplitter::new();
         for element in self.elements {
@@ -530,6 +1858,7 @@ impl PathBuilder {
                 PathElement::MoveTo(p) => splitter.move_to(p),
                 PathElement::LineTo(p) => splitter.line_to(p),
                 PathElement::QuadTo(p1, p2) => splitter.quad_to(p1, p2),
+                PathElement::CubicTo(p1, p2, p3) => splitter.cubic_to(p1, p2, p3),
                 PathElement::ArcTo(center, p1) => splitter.arc_to(center, p1),
                 PathElement::Close => splitter.close(),
             };
@@ -578,6 +1907,17 @@ impl PathSplitter {
         self
     }
 
+    pub fn cubic_to(mut self, p1: glm::Vec2, p2: glm::Vec2, p3: glm::Vec2) -> Self {
+        self.curves.push(Curve::Cubic {
+            p0: self.last,
+            p1,
+            p2,
+            p3,
+        });
+        self.last = p3;
+        self
+    }
+
     // spooky
     fn arc_to(mut self, center: glm::Vec2, p1: glm::Vec2) -> Self {
         self.curves.push(Curve::Arc {
@@ -0,0 +1,977 @@
+use crate::path::{rounded_rect_radii, shadow_rect, QUAD_TOLERANCE};
+use crate::{
+    glm, BlendMode, Brush, Color, Curve, DecodedDraw, DrawCommand, FillRule, GpuData, GradientStop,
+    PathBuilder, SpreadMode,
+};
+use std::error::Error;
+use std::fmt::{self, Write};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ParseErrorKind {
+    UnexpectedCharacter(char),
+    UnexpectedEnd,
+    ExpectedNumber,
+    ExpectedFlag,
+    MissingMoveTo,
+}
+
+/// Error while parsing SVG path data.
+///
+/// `position` is the byte offset of the offending character in the input.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ParseError {
+    pub position: usize,
+    pub kind: ParseErrorKind,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            ParseErrorKind::UnexpectedCharacter(c) => {
+                write!(
+                    f,
+                    "unexpected character {:?} at position {}",
+                    c, self.position
+                )
+            }
+            ParseErrorKind::UnexpectedEnd => {
+                write!(
+                    f,
+                    "unexpected end of path data at position {}",
+                    self.position
+                )
+            }
+            ParseErrorKind::ExpectedNumber => {
+                write!(f, "expected number at position {}", self.position)
+            }
+            ParseErrorKind::ExpectedFlag => {
+                write!(
+                    f,
+                    "expected arc flag (0 or 1) at position {}",
+                    self.position
+                )
+            }
+            ParseErrorKind::MissingMoveTo => {
+                write!(
+                    f,
+                    "path data must start with a moveto at position {}",
+                    self.position
+                )
+            }
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+struct Parser<'a> {
+    data: &'a str,
+    pos: usize,
+    // a comma after the last operand has to be followed by another operand
+    comma: bool,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<char> {
+        self.data[self.pos..].chars().next()
+    }
+
+    fn error(&self, kind: ParseErrorKind) -> ParseError {
+        ParseError {
+            position: self.pos,
+            kind,
+        }
+    }
+
+    fn unexpected(&self) -> ParseError {
+        match self.peek() {
+            Some(c) => self.error(ParseErrorKind::UnexpectedCharacter(c)),
+            None => self.error(ParseErrorKind::UnexpectedEnd),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek() {
+            if c.is_ascii_whitespace() {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    // whitespace with at most one comma
+    fn skip_separator(&mut self) {
+        self.skip_whitespace();
+        self.comma = self.peek() == Some(',');
+        if self.comma {
+            self.pos += 1;
+            self.skip_whitespace();
+        }
+    }
+
+    fn at_number(&self) -> bool {
+        match self.peek() {
+            Some(c) => c.is_ascii_digit() || c == '-' || c == '+' || c == '.',
+            None => false,
+        }
+    }
+
+    fn skip_digits(&mut self) -> usize {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if c.is_ascii_digit() {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+        self.pos - start
+    }
+
+    fn number(&mut self) -> Result<f32, ParseError> {
+        self.skip_whitespace();
+        let start = self.pos;
+
+        if let Some('-') | Some('+') = self.peek() {
+            self.pos += 1;
+        }
+
+        let mut digits = self.skip_digits();
+        if self.peek() == Some('.') {
+            self.pos += 1;
+            digits += self.skip_digits();
+        }
+
+        if digits == 0 {
+            self.pos = start;
+            return Err(self.error(match self.peek() {
+                Some(_) => ParseErrorKind::ExpectedNumber,
+                None => ParseErrorKind::UnexpectedEnd,
+            }));
+        }
+
+        // exponent, only if followed by digits (`1em` is not a number)
+        if let Some('e') | Some('E') = self.peek() {
+            let mantissa_end = self.pos;
+            self.pos += 1;
+            if let Some('-') | Some('+') = self.peek() {
+                self.pos += 1;
+            }
+            if self.skip_digits() == 0 {
+                self.pos = mantissa_end;
+            }
+        }
+
+        let value = self.data[start..self.pos]
+            .parse::<f32>()
+            .map_err(|_| ParseError {
+                position: start,
+                kind: ParseErrorKind::ExpectedNumber,
+            })?;
+
+        self.skip_separator();
+        Ok(value)
+    }
+
+    fn point(&mut self) -> Result<glm::Vec2, ParseError> {
+        let x = self.number()?;
+        let y = self.number()?;
+        Ok(glm::vec2(x, y))
+    }
+
+    fn flag(&mut self) -> Result<bool, ParseError> {
+        self.skip_whitespace();
+        let flag = match self.peek() {
+            Some('0') => false,
+            Some('1') => true,
+            Some(_) => return Err(self.error(ParseErrorKind::ExpectedFlag)),
+            None => return Err(self.error(ParseErrorKind::UnexpectedEnd)),
+        };
+        self.pos += 1;
+        self.skip_separator();
+        Ok(flag)
+    }
+}
+
+impl PathBuilder {
+    /// Parse SVG path data (the `d` attribute of a `<path>` element).
+    ///
+    /// Supports all absolute and relative commands (`M L H V Q T C S A Z`).
+    /// Coordinates are taken as is, no y-axis flip is applied.
+    pub fn from_svg_path(data: &str) -> Result<PathBuilder, ParseError> {
+        let mut parser = Parser {
+            data,
+            pos: 0,
+            comma: false,
+        };
+        let mut builder = PathBuilder::new();
+
+        let mut current = glm::vec2(0.0f32, 0.0f32);
+        let mut start = current;
+        let mut closed = false;
+
+        // reflected control points for smooth curves (`S`, `T`)
+        let mut last_cubic_ctrl: Option<glm::Vec2> = None;
+        let mut last_quad_ctrl: Option<glm::Vec2> = None;
+
+        let mut command: Option<char> = None;
+
+        loop {
+            parser.skip_whitespace();
+
+            let c = match parser.peek() {
+                Some(c) => c,
+                None if parser.comma => return Err(parser.unexpected()),
+                None => break,
+            };
+
+            let cmd = if c.is_ascii_alphabetic() {
+                if parser.comma {
+                    return Err(parser.unexpected());
+                }
+                match c {
+                    'M' | 'm' | 'L' | 'l' | 'H' | 'h' | 'V' | 'v' | 'Q' | 'q' | 'T' | 't' | 'C'
+                    | 'c' | 'S' | 's' | 'A' | 'a' | 'Z' | 'z' => (),
+                    _ => return Err(parser.unexpected()),
+                }
+                parser.pos += 1;
+                c
+            } else {
+                // implicit repetition of the previous command
+                match command {
+                    Some('Z') | Some('z') | None => return Err(parser.unexpected()),
+                    Some('M') if parser.at_number() => 'L',
+                    Some('m') if parser.at_number() => 'l',
+                    Some(cmd) if parser.at_number() => cmd,
+                    Some(_) => return Err(parser.unexpected()),
+                }
+            };
+
+            if command.is_none() && cmd != 'M' && cmd != 'm' {
+                return Err(ParseError {
+                    position: parser.pos - 1,
+                    kind: ParseErrorKind::MissingMoveTo,
+                });
+            }
+            command = Some(cmd);
+
+            let relative = cmd.is_ascii_lowercase();
+            let base = if relative {
+                current
+            } else {
+                glm::vec2(0.0, 0.0)
+            };
+
+            // continue the closed subpath at its initial point
+            if closed && cmd != 'M' && cmd != 'm' && cmd != 'Z' && cmd != 'z' {
+                builder = builder.move_to(start);
+            }
+            closed = false;
+
+            let mut cubic_ctrl = None;
+            let mut quad_ctrl = None;
+
+            match cmd.to_ascii_uppercase() {
+                'M' => {
+                    let p = base + parser.point()?;
+                    builder = builder.move_to(p);
+                    current = p;
+                    start = p;
+                }
+                'L' => {
+                    let p = base + parser.point()?;
+                    builder = builder.line_to(p);
+                    current = p;
+                }
+                'H' => {
+                    let p = glm::vec2(base.x + parser.number()?, current.y);
+                    builder = builder.line_to(p);
+                    current = p;
+                }
+                'V' => {
+                    let p = glm::vec2(current.x, base.y + parser.number()?);
+                    builder = builder.line_to(p);
+                    current = p;
+                }
+                'Q' => {
+                    let p1 = base + parser.point()?;
+                    let p2 = base + parser.point()?;
+                    builder = builder.quad_to(p1, p2);
+                    quad_ctrl = Some(p1);
+                    current = p2;
+                }
+                'T' => {
+                    let p1 = match last_quad_ctrl {
+                        Some(ctrl) => 2.0 * current - ctrl,
+                        None => current,
+                    };
+                    let p2 = base + parser.point()?;
+                    builder = builder.quad_to(p1, p2);
+                    quad_ctrl = Some(p1);
+                    current = p2;
+                }
+                'C' => {
+                    let p1 = base + parser.point()?;
+                    let p2 = base + parser.point()?;
+                    let p3 = base + parser.point()?;
+                    builder = builder.cubic_to(p1, p2, p3);
+                    cubic_ctrl = Some(p2);
+                    current = p3;
+                }
+                'S' => {
+                    let p1 = match last_cubic_ctrl {
+                        Some(ctrl) => 2.0 * current - ctrl,
+                        None => current,
+                    };
+                    let p2 = base + parser.point()?;
+                    let p3 = base + parser.point()?;
+                    builder = builder.cubic_to(p1, p2, p3);
+                    cubic_ctrl = Some(p2);
+                    current = p3;
+                }
+                'A' => {
+                    let rx = parser.number()?;
+                    let ry = parser.number()?;
+                    let x_rotation = parser.number()?;
+                    let large_arc = parser.flag()?;
+                    let sweep = parser.flag()?;
+                    let p = base + parser.point()?;
+                    builder = builder.elliptical_arc_to(
+                        glm::vec2(rx, ry),
+                        x_rotation.to_radians(),
+                        large_arc,
+                        sweep,
+                        p,
+                    );
+                    current = p;
+                }
+                'Z' => {
+                    builder = builder.close();
+                    current = start;
+                    closed = true;
+                }
+                _ => unreachable!(),
+            }
+
+            last_cubic_ctrl = cubic_ctrl;
+            last_quad_ctrl = quad_ctrl;
+        }
+
+        Ok(builder)
+    }
+}
+
+// Contour of a rect in the orientation of its coverage, see `Curve::transform`.
+fn rect_corners(p0: glm::Vec2, p1: glm::Vec2) -> [glm::Vec2; 4] {
+    [p0, glm::vec2(p0.x, p1.y), p1, glm::vec2(p1.x, p0.y)]
+}
+
+// Closed polygon with circular corners, `radii` in the order of `corners`.
+fn write_rounded_polygon(d: &mut String, corners: &[glm::Vec2; 4], radii: [f32; 4]) {
+    for i in 0..4 {
+        let c = corners[i];
+        let prev = corners[(i + 3) % 4];
+        let next = corners[(i + 1) % 4];
+        let r = radii[i];
+        if r > 0.0 {
+            let start = c + r * glm::normalize(&(prev - c));
+            let end = c + r * glm::normalize(&(next - c));
+            let turn = (c - prev).x * (next - c).y - (c - prev).y * (next - c).x;
+            let cmd = if i == 0 { 'M' } else { 'L' };
+            write!(d, "{} {} {} ", cmd, start.x, start.y).unwrap();
+            write!(
+                d,
+                "A {} {} 0 0 {} {} {} ",
+                r,
+                r,
+                (turn > 0.0) as u8,
+                end.x,
+                end.y
+            )
+            .unwrap();
+        } else {
+            let cmd = if i == 0 { 'M' } else { 'L' };
+            write!(d, "{} {} {} ", cmd, c.x, c.y).unwrap();
+        }
+    }
+    d.push_str("Z ");
+}
+
+// Start and end point of curves which are part of a contour.
+fn endpoints(curve: &Curve) -> Option<(glm::Vec2, glm::Vec2)> {
+    match *curve {
+        Curve::Line { p0, p1 } | Curve::Arc { p0, p1, .. } => Some((p0, p1)),
+        Curve::Quad { p0, p2, .. } => Some((p0, p2)),
+        Curve::Cubic { p0, p3, .. } => Some((p0, p3)),
+        _ => None,
+    }
+}
+
+// Points are equal up to the precision of the f16 encoding.
+fn close_to(a: glm::Vec2, b: glm::Vec2) -> bool {
+    let tolerance = 2e-3 * (1.0 + a.x.abs().max(a.y.abs()));
+    (a.x - b.x).abs() <= tolerance && (a.y - b.y).abs() <= tolerance
+}
+
+// Contour curve continuing at the current point.
+fn write_segment(d: &mut String, curve: &Curve) {
+    match *curve {
+        Curve::Line { p1, .. } => write!(d, "L {} {} ", p1.x, p1.y).unwrap(),
+        Curve::Quad { p1, p2, .. } => write!(d, "Q {} {} {} {} ", p1.x, p1.y, p2.x, p2.y).unwrap(),
+        Curve::Cubic { p1, p2, p3, .. } => write!(
+            d,
+            "C {} {} {} {} {} {} ",
+            p1.x, p1.y, p2.x, p2.y, p3.x, p3.y
+        )
+        .unwrap(),
+        Curve::Arc { center, p0, p1 } => {
+            let d0 = p0 - center;
+            let d1 = p1 - center;
+            let r = glm::length(&d0);
+            if (r - glm::length(&d1)).abs() > QUAD_TOLERANCE {
+                // SVG arcs are circular, the radius of `Curve::Arc` is interpolated
+                for quad in curve.to_quads(QUAD_TOLERANCE) {
+                    write_segment(d, &quad);
+                }
+                return;
+            }
+            let sweep = d0.x * d1.y - d0.y * d1.x > 0.0;
+            write!(d, "A {} {} 0 0 {} {} {} ", r, r, sweep as u8, p1.x, p1.y).unwrap();
+        }
+        _ => unreachable!(),
+    }
+}
+
+/// SVG path data of `curves`.
+///
+/// SVG closes every subpath, so connected curves are chained into contours first.
+/// Shapes (`Circle`, `Rect`, ...) become closed subpaths with the same orientation
+/// as their coverage in the shader.
+fn path_data(curves: &[Curve]) -> String {
+    let mut d = String::new();
+
+    let mut used = vec![false; curves.len()];
+    for (i, curve) in curves.iter().enumerate() {
+        let (start, mut end) = match endpoints(curve) {
+            Some(endpoints) if !used[i] => endpoints,
+            _ => continue,
+        };
+        used[i] = true;
+        write!(d, "M {} {} ", start.x, start.y).unwrap();
+        write_segment(&mut d, curve);
+
+        while !close_to(start, end) {
+            let next = (0..curves.len()).find(|&j| {
+                !used[j] && endpoints(&curves[j]).map_or(false, |(p0, _)| close_to(p0, end))
+            });
+            match next {
+                Some(j) => {
+                    used[j] = true;
+                    write_segment(&mut d, &curves[j]);
+                    end = endpoints(&curves[j]).unwrap().1;
+                }
+                None => break,
+            }
+        }
+    }
+
+    for curve in curves {
+        match *curve {
+            Curve::Circle { center, radius } => {
+                // clockwise, matching the positive coverage of the shader
+                let (x, y) = (center.x, center.y);
+                write!(
+                    d,
+                    "M {} {} A {r} {r} 0 1 0 {} {} A {r} {r} 0 1 0 {} {} Z ",
+                    x + radius,
+                    y,
+                    x - radius,
+                    y,
+                    x + radius,
+                    y,
+                    r = radius
+                )
+                .unwrap();
+            }
+            Curve::Ellipse {
+                center,
+                radii,
+                rotation,
+            } => {
+                let axis = radii.x * glm::vec2(rotation.cos(), rotation.sin());
+                let (a, b) = (center + axis, center - axis);
+                let angle = rotation.to_degrees();
+                write!(
+                    d,
+                    "M {} {} A {rx} {ry} {angle} 1 0 {} {} A {rx} {ry} {angle} 1 0 {} {} Z ",
+                    a.x,
+                    a.y,
+                    b.x,
+                    b.y,
+                    a.x,
+                    a.y,
+                    rx = radii.x,
+                    ry = radii.y,
+                    angle = angle
+                )
+                .unwrap();
+            }
+            Curve::Rect { p0, p1 } => {
+                write_rounded_polygon(&mut d, &rect_corners(p0, p1), [0.0; 4]);
+            }
+            Curve::RoundedRect { p0, p1, radii } => {
+                let r = rounded_rect_radii(p0, p1, radii);
+                write_rounded_polygon(&mut d, &rect_corners(p0, p1), [r[0], r[3], r[2], r[1]]);
+            }
+            Curve::ShadowRect {
+                p0,
+                p1,
+                radius,
+                spread,
+                ..
+            } => {
+                let (p0, p1, radius) = shadow_rect(p0, p1, radius, spread);
+                write_rounded_polygon(&mut d, &rect_corners(p0, p1), [radius; 4]);
+            }
+            _ => (),
+        }
+    }
+
+    d.pop();
+    d
+}
+
+fn fill_rule(fill_rule: FillRule) -> &'static str {
+    match fill_rule {
+        FillRule::NonZero => "nonzero",
+        FillRule::EvenOdd => "evenodd",
+    }
+}
+
+fn spread_method(spread: SpreadMode) -> &'static str {
+    match spread {
+        SpreadMode::Pad => "pad",
+        SpreadMode::Repeat => "repeat",
+        SpreadMode::Reflect => "reflect",
+    }
+}
+
+// CSS `mix-blend-mode` other than `normal`, Porter-Duff operators other than source-over
+// have no equivalent.
+fn mix_blend_mode(blend_mode: BlendMode) -> Option<&'static str> {
+    match blend_mode {
+        BlendMode::Plus => Some("plus-lighter"),
+        BlendMode::Multiply => Some("multiply"),
+        BlendMode::Screen => Some("screen"),
+        BlendMode::Darken => Some("darken"),
+        BlendMode::Lighten => Some("lighten"),
+        _ => None,
+    }
+}
+
+// Opening tag of a group, unsupported blend modes fall back to source-over.
+fn write_group(svg: &mut String, attributes: &str, blend_mode: BlendMode) {
+    if blend_mode == BlendMode::SrcOver {
+        write!(svg, "<g {}>", attributes).unwrap();
+        return;
+    }
+
+    match mix_blend_mode(blend_mode) {
+        Some(mode) => write!(svg, "<g {} style=\"mix-blend-mode:{}\">", attributes, mode).unwrap(),
+        None => write!(
+            svg,
+            "<!-- unsupported blend mode {:?} --><g {}>",
+            blend_mode, attributes
+        )
+        .unwrap(),
+    }
+}
+
+// Brush colors are linear values written to an sRGB target, SVG colors are sRGB.
+fn srgb_color(color: Color) -> Color {
+    let a = color[3] as f32 / 255.0;
+    crate::cpu::encode_pixel(glm::vec4(
+        a * color[0] as f32 / 255.0,
+        a * color[1] as f32 / 255.0,
+        a * color[2] as f32 / 255.0,
+        a,
+    ))
+}
+
+fn solid_paint(color: Color) -> String {
+    let [r, g, b, a] = srgb_color(color);
+    format!(
+        "fill=\"rgb({},{},{})\" fill-opacity=\"{}\"",
+        r,
+        g,
+        b,
+        a as f32 / 255.0
+    )
+}
+
+fn write_stops(svg: &mut String, stops: &[GradientStop]) {
+    for stop in stops {
+        let [r, g, b, a] = srgb_color(stop.color);
+        write!(
+            svg,
+            "<stop offset=\"{}\" stop-color=\"rgb({},{},{})\" stop-opacity=\"{}\"/>",
+            stop.offset,
+            r,
+            g,
+            b,
+            a as f32 / 255.0
+        )
+        .unwrap();
+    }
+}
+
+// Writes paint servers of `brush` and returns the fill attributes.
+fn write_paint(svg: &mut String, id: &str, brush: &Brush) -> String {
+    match *brush {
+        Brush::Color(color) => solid_paint(color),
+        Brush::LinearGradient {
+            start,
+            end,
+            ref stops,
+            spread,
+        } => {
+            write!(
+                svg,
+                "<linearGradient id=\"{}\" gradientUnits=\"userSpaceOnUse\" x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" spreadMethod=\"{}\">",
+                id, start.x, start.y, end.x, end.y, spread_method(spread)
+            )
+            .unwrap();
+            write_stops(svg, stops);
+            svg.push_str("</linearGradient>\n");
+            format!("fill=\"url(#{})\"", id)
+        }
+        Brush::RadialGradient {
+            center,
+            radius,
+            focal,
+            ref stops,
+            spread,
+        } => {
+            write!(
+                svg,
+                "<radialGradient id=\"{}\" gradientUnits=\"userSpaceOnUse\" cx=\"{}\" cy=\"{}\" r=\"{}\" fx=\"{}\" fy=\"{}\" spreadMethod=\"{}\">",
+                id, center.x, center.y, radius, focal.x, focal.y, spread_method(spread)
+            )
+            .unwrap();
+            write_stops(svg, stops);
+            svg.push_str("</radialGradient>\n");
+            format!("fill=\"url(#{})\"", id)
+        }
+        Brush::SweepGradient { ref stops, .. } => {
+            // no conic gradients in SVG, approximated by the first stop
+            svg.push_str("<!-- sweep gradient approximated by its first stop -->\n");
+            solid_paint(stops.first().map(|stop| stop.color).unwrap_or([0; 4]))
+        }
+        Brush::Image { image_id, .. } => {
+            // atlas contents aren't part of `GpuData`
+            writeln!(svg, "<!-- image {} not exported -->", image_id).unwrap();
+            solid_paint([255, 0, 255, 255])
+        }
+    }
+}
+
+impl GpuData {
+    /// Serialize the encoded draws as an SVG document, e.g. for debugging.
+    ///
+    /// Each draw becomes a `<path>` clipped to its rect and clip paths, layers become groups.
+    /// World space has the y-axis pointing up, the document flips it. Gradients and blending
+    /// happen in sRGB in SVG instead of linear space, sweep gradients and images are replaced
+    /// by placeholders.
+    pub fn to_svg(&self) -> String {
+        let draws = self.decode();
+
+        let mut min = glm::vec2(std::f32::MAX, std::f32::MAX);
+        let mut max = glm::vec2(std::f32::MIN, std::f32::MIN);
+        for vertex in self.bbox.chunks(4) {
+            min = glm::min2(&min, &glm::vec2(vertex[0], vertex[1]));
+            max = glm::max2(&max, &glm::vec2(vertex[0], vertex[1]));
+        }
+        if min.x > max.x {
+            min = glm::vec2(0.0, 0.0);
+            max = min;
+        }
+
+        let mut svg = String::new();
+        writeln!(
+            svg,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{} {} {} {}\">",
+            min.x,
+            -max.y,
+            max.x - min.x,
+            max.y - min.y
+        )
+        .unwrap();
+        svg.push_str("<g transform=\"scale(1 -1)\">\n");
+
+        for command in self.balanced_commands() {
+            match command {
+                DrawCommand::Draw {
+                    start,
+                    end,
+                    blend_mode,
+                } => {
+                    for i in start as usize / 6..end as usize / 6 {
+                        match draws.get(i) {
+                            Some(draw) if draw.is_valid() => {
+                                write_draw(&mut svg, i, draw, blend_mode)
+                            }
+                            _ => writeln!(svg, "<!-- draw {} is invalid -->", i).unwrap(),
+                        }
+                    }
+                }
+                DrawCommand::PushLayer {
+                    opacity,
+                    blend_mode,
+                } => {
+                    write_group(&mut svg, &format!("opacity=\"{}\"", opacity), blend_mode);
+                    svg.push('\n');
+                }
+                DrawCommand::PopLayer => svg.push_str("</g>\n"),
+            }
+        }
+
+        svg.push_str("</g>\n</svg>\n");
+        svg
+    }
+}
+
+fn write_draw(svg: &mut String, i: usize, draw: &DecodedDraw, blend_mode: BlendMode) {
+    let to_local = draw.curve_to_local();
+    let rect = &draw.bbox;
+    let p0 = rect.offset_local;
+    let p1 = rect.offset_local + rect.extent_local;
+    let min = glm::min2(&p0, &p1);
+
+    // mirrored rects have negative extents
+    writeln!(
+        svg,
+        "<clipPath id=\"rect{}\"><rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"/></clipPath>",
+        i,
+        min.x,
+        min.y,
+        rect.extent_local.x.abs(),
+        rect.extent_local.y.abs()
+    )
+    .unwrap();
+    for (j, (curves, rule)) in draw.clips.iter().enumerate() {
+        let curves = Curve::transform_path(curves, &to_local);
+        writeln!(
+            svg,
+            "<clipPath id=\"clip{}-{}\"><path d=\"{}\" clip-rule=\"{}\"/></clipPath>",
+            i,
+            j,
+            path_data(&curves),
+            fill_rule(*rule)
+        )
+        .unwrap();
+    }
+    // valid draws always have a brush
+    let brush = draw.brush.as_ref().unwrap();
+    let fill = write_paint(svg, &format!("paint{}", i), brush);
+
+    write_group(svg, &format!("clip-path=\"url(#rect{})\"", i), blend_mode);
+    for j in 0..draw.clips.len() {
+        write!(svg, "<g clip-path=\"url(#clip{}-{})\">", i, j).unwrap();
+    }
+
+    let curves = Curve::transform_path(&draw.curves, &to_local);
+    match curves[..] {
+        [Curve::Circle { center, radius }] => write!(
+            svg,
+            "<circle cx=\"{}\" cy=\"{}\" r=\"{}\"",
+            center.x, center.y, radius
+        )
+        .unwrap(),
+        [Curve::Rect { p0, p1 }] => write!(
+            svg,
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"",
+            p0.x.min(p1.x),
+            p0.y.min(p1.y),
+            (p1.x - p0.x).abs(),
+            (p1.y - p0.y).abs()
+        )
+        .unwrap(),
+        [Curve::ShadowRect {
+            p0,
+            p1,
+            radius,
+            sigma,
+            spread,
+        }] => {
+            let (p0, p1, radius) = shadow_rect(p0, p1, radius, spread);
+            let (min, max) = (glm::min2(&p0, &p1), glm::max2(&p0, &p1));
+            write!(
+                svg,
+                "<filter id=\"blur{}\" filterUnits=\"userSpaceOnUse\" x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"><feGaussianBlur stdDeviation=\"{}\"/></filter>",
+                i,
+                min.x - 3.0 * sigma,
+                min.y - 3.0 * sigma,
+                max.x - min.x + 6.0 * sigma,
+                max.y - min.y + 6.0 * sigma,
+                sigma
+            )
+            .unwrap();
+            write!(
+                svg,
+                "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" rx=\"{}\" filter=\"url(#blur{})\"",
+                min.x,
+                min.y,
+                max.x - min.x,
+                max.y - min.y,
+                radius,
+                i
+            )
+            .unwrap()
+        }
+        _ => write!(
+            svg,
+            "<path d=\"{}\" fill-rule=\"{}\"",
+            path_data(&curves),
+            fill_rule(draw.fill_rule)
+        )
+        .unwrap(),
+    }
+    write!(svg, " {}/>", fill).unwrap();
+
+    for _ in 0..draw.clips.len() {
+        svg.push_str("</g>");
+    }
+    svg.push_str("</g>\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(data: &str) -> String {
+        let curves = PathBuilder::from_svg_path(data).unwrap().fill().finish();
+        format!("{:?}", curves)
+    }
+
+    fn build(builder: PathBuilder) -> String {
+        format!("{:?}", builder.fill().finish())
+    }
+
+    fn parse_error(data: &str) -> ParseError {
+        match PathBuilder::from_svg_path(data) {
+            Ok(_) => panic!("{:?} should not parse", data),
+            Err(err) => err,
+        }
+    }
+
+    #[test]
+    fn implicit_repetition() {
+        let expected = build(
+            PathBuilder::new()
+                .move_to(glm::vec2(0.0, 0.0))
+                .line_to(glm::vec2(10.0, 0.0))
+                .line_to(glm::vec2(10.0, 10.0))
+                .close(),
+        );
+        assert_eq!(parse("M 0 0 10 0 10 10 Z"), expected);
+        assert_eq!(parse("M0,0 L10,0 10,10z"), expected);
+        assert_eq!(parse("M0,0L10,0,10,10Z"), expected);
+    }
+
+    #[test]
+    fn relative_commands() {
+        let expected = build(
+            PathBuilder::new()
+                .move_to(glm::vec2(1.0, 2.0))
+                .line_to(glm::vec2(4.0, 2.0))
+                .line_to(glm::vec2(4.0, 6.0))
+                .quad_to(glm::vec2(5.0, 7.0), glm::vec2(6.0, 6.0))
+                .close(),
+        );
+        assert_eq!(parse("m 1 2 3 0 v 4 q 1 1 2 0 z"), expected);
+        assert_eq!(parse("M1,2 h3 l0,4 q1,1,2,0 Z"), expected);
+    }
+
+    #[test]
+    fn numbers() {
+        let expected = build(
+            PathBuilder::new()
+                .move_to(glm::vec2(0.5, -0.5))
+                .line_to(glm::vec2(100.0, 0.25)),
+        );
+        assert_eq!(parse("M.5-.5L1e2.25"), expected);
+        assert_eq!(parse("M 0.5 , -0.5 L 1E+2 , 25e-2"), expected);
+    }
+
+    #[test]
+    fn error_kinds() {
+        assert_eq!(
+            parse_error("L 0 0"),
+            ParseError {
+                position: 0,
+                kind: ParseErrorKind::MissingMoveTo,
+            }
+        );
+        assert_eq!(
+            parse_error("M 0 0 X"),
+            ParseError {
+                position: 6,
+                kind: ParseErrorKind::UnexpectedCharacter('X'),
+            }
+        );
+        assert_eq!(
+            parse_error("M 0 0 L 1"),
+            ParseError {
+                position: 9,
+                kind: ParseErrorKind::UnexpectedEnd,
+            }
+        );
+        assert_eq!(
+            parse_error("M 0 0 L 1 a"),
+            ParseError {
+                position: 10,
+                kind: ParseErrorKind::ExpectedNumber,
+            }
+        );
+        assert_eq!(
+            parse_error("M 0 0 A 1 1 0 2 0 1 1"),
+            ParseError {
+                position: 14,
+                kind: ParseErrorKind::ExpectedFlag,
+            }
+        );
+    }
+
+    #[test]
+    fn double_commas() {
+        assert_eq!(
+            parse_error("M 0,,0"),
+            ParseError {
+                position: 4,
+                kind: ParseErrorKind::ExpectedNumber,
+            }
+        );
+        assert_eq!(
+            parse_error("M 0 0 L 1,2,,3,4"),
+            ParseError {
+                position: 12,
+                kind: ParseErrorKind::UnexpectedCharacter(','),
+            }
+        );
+        assert_eq!(
+            parse_error("M 0 0, L 1 2"),
+            ParseError {
+                position: 7,
+                kind: ParseErrorKind::UnexpectedCharacter('L'),
+            }
+        );
+        assert_eq!(
+            parse_error("M 0 0,"),
+            ParseError {
+                position: 6,
+                kind: ParseErrorKind::UnexpectedEnd,
+            }
+        );
+    }
+}
@@ -0,0 +1,97 @@
+use crate::glm;
+
+/// 2D affine transformation `p' = matrix * p + translation`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Transform {
+    pub matrix: glm::Mat2,
+    pub translation: glm::Vec2,
+}
+
+impl Transform {
+    pub fn identity() -> Self {
+        Transform {
+            matrix: glm::mat2(1.0, 0.0, 0.0, 1.0),
+            translation: glm::vec2(0.0, 0.0),
+        }
+    }
+
+    pub fn translate(offset: glm::Vec2) -> Self {
+        Transform {
+            translation: offset,
+            ..Transform::identity()
+        }
+    }
+
+    /// Counter-clockwise rotation, `angle` in radians.
+    pub fn rotate(angle: f32) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Transform {
+            matrix: glm::mat2(cos, -sin, sin, cos),
+            ..Transform::identity()
+        }
+    }
+
+    pub fn scale(sx: f32, sy: f32) -> Self {
+        Transform {
+            matrix: glm::mat2(sx, 0.0, 0.0, sy),
+            ..Transform::identity()
+        }
+    }
+
+    /// Skew along the x and y axis, angles in radians.
+    pub fn skew(ax: f32, ay: f32) -> Self {
+        Transform {
+            matrix: glm::mat2(1.0, ax.tan(), ay.tan(), 1.0),
+            ..Transform::identity()
+        }
+    }
+
+    /// Transformation applying `self` first, followed by `other`.
+    pub fn then(&self, other: &Transform) -> Transform {
+        Transform {
+            matrix: other.matrix * self.matrix,
+            translation: other.matrix * self.translation + other.translation,
+        }
+    }
+
+    pub fn inverse(&self) -> Option<Transform> {
+        let matrix = self.matrix.try_inverse()?;
+        Some(Transform {
+            matrix,
+            translation: -(matrix * self.translation),
+        })
+    }
+
+    pub fn apply(&self, p: glm::Vec2) -> glm::Vec2 {
+        self.matrix * p + self.translation
+    }
+
+    /// Transform a direction, ignoring the translation.
+    pub fn apply_vector(&self, v: glm::Vec2) -> glm::Vec2 {
+        self.matrix * v
+    }
+
+    /// Scale factor if the transformation preserves circles (rotation, uniform scale,
+    /// reflection and translation only).
+    pub fn uniform_scale(&self) -> Option<f32> {
+        const EPSILON: f32 = 1e-5;
+
+        let x = glm::vec2(self.matrix[(0, 0)], self.matrix[(1, 0)]);
+        let y = glm::vec2(self.matrix[(0, 1)], self.matrix[(1, 1)]);
+
+        let sx = glm::length2(&x);
+        let sy = glm::length2(&y);
+        let max = sx.max(sy);
+
+        if (sx - sy).abs() <= EPSILON * max && glm::dot(&x, &y).abs() <= EPSILON * max {
+            Some(sx.sqrt())
+        } else {
+            None
+        }
+    }
+
+    /// Whether axis-aligned rectangles stay axis-aligned.
+    pub fn is_axis_aligned(&self) -> bool {
+        self.matrix[(0, 1)] == 0.0 && self.matrix[(1, 0)] == 0.0
+    }
+}
@@ -0,0 +1,549 @@
+// Golden-image tests: scenes are rendered with the CPU reference rasterizer and compared
+// against the PNGs in `tests/golden`.
+//
+// The references are blessed from `cpu::render` itself, so these tests only catch changes of the
+// CPU rasterizer, not divergence between it and `lanka.fs`.
+//
+// References are only written when `GOLDEN_BLESS=1` is set, for new scenes or after an intended
+// change. On mismatch the rendered image and a diff image (mismatching pixels
+// in red) are written to `target/golden`.
+
+use grr_2d::GlyphPositioner;
+use image::RgbaImage;
+use nalgebra_glm as glm;
+use std::path::PathBuf;
+
+// maximum per-channel difference of a matching pixel
+const TOLERANCE: u8 = 2;
+
+fn golden_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden")
+}
+
+fn output_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target/golden")
+}
+
+fn check(name: &str, gpu_data: &grr_2d::GpuData, width: u32, height: u32) {
+    let aabb = bounds(gpu_data);
+    let center = 0.5 * (aabb.min + aabb.max);
+    let extent = aabb.max - aabb.min;
+    let aspect_ratio = width as f32 / height as f32;
+    let viewport = grr_2d::Viewport {
+        position: (center.x, center.y),
+        scaling_y: extent.y.max(extent.x / aspect_ratio),
+        aspect_ratio,
+    };
+    let image = grr_2d::cpu::render(gpu_data, &viewport, width, height);
+
+    let reference_path = golden_dir().join(format!("{}.png", name));
+    if std::env::var_os("GOLDEN_BLESS").is_some() {
+        std::fs::create_dir_all(golden_dir()).unwrap();
+        image.save(&reference_path).unwrap();
+        return;
+    }
+    assert!(
+        reference_path.exists(),
+        "{}: missing reference, run with GOLDEN_BLESS=1",
+        name
+    );
+
+    let reference = image::open(&reference_path).unwrap().to_rgba8();
+    assert_eq!(
+        reference.dimensions(),
+        image.dimensions(),
+        "{}: size differs from the reference",
+        name
+    );
+
+    let mut diff = RgbaImage::new(width, height);
+    let mut mismatches = 0;
+    for ((actual, expected), d) in image
+        .pixels()
+        .zip(reference.pixels())
+        .zip(diff.pixels_mut())
+    {
+        let delta = actual
+            .0
+            .iter()
+            .zip(&expected.0)
+            .map(|(&a, &b)| (a as i32 - b as i32).abs() as u8)
+            .max()
+            .unwrap();
+
+        if delta > TOLERANCE {
+            mismatches += 1;
+            d.0 = [255, 0, 0, 255];
+        } else {
+            // faded reference for orientation
+            let luma = (expected.0[0] as u32 + expected.0[1] as u32 + expected.0[2] as u32) / 3;
+            let luma = (luma * expected.0[3] as u32 + 255 * (255 - expected.0[3] as u32)) / 255;
+            let faded = (128 + luma / 2) as u8;
+            d.0 = [faded, faded, faded, 255];
+        }
+    }
+
+    if mismatches > 0 {
+        std::fs::create_dir_all(output_dir()).unwrap();
+        let actual_path = output_dir().join(format!("{}.png", name));
+        let diff_path = output_dir().join(format!("{}.diff.png", name));
+        image.save(&actual_path).unwrap();
+        diff.save(&diff_path).unwrap();
+        panic!(
+            "{}: {} pixels differ from the reference, see {} and {}",
+            name,
+            mismatches,
+            actual_path.display(),
+            diff_path.display()
+        );
+    }
+}
+
+// world space bounds of all draws
+fn bounds(gpu_data: &grr_2d::GpuData) -> grr_2d::Aabb {
+    let mut aabb = grr_2d::Aabb {
+        min: glm::vec2(std::f32::MAX, std::f32::MAX),
+        max: glm::vec2(std::f32::MIN, std::f32::MIN),
+    };
+    for vertex in gpu_data.bbox.chunks(4) {
+        aabb.min = glm::min2(&aabb.min, &glm::vec2(vertex[0], vertex[1]));
+        aabb.max = glm::max2(&aabb.max, &glm::vec2(vertex[0], vertex[1]));
+    }
+    aabb
+}
+
+fn fill(gpu_data: &mut grr_2d::GpuData, curves: &[grr_2d::Curve], brush: grr_2d::Brush) {
+    let aabb = grr_2d::Aabb::from_curves(curves);
+    gpu_data.extend(
+        curves,
+        grr_2d::Rect {
+            offset_local: aabb.min,
+            extent_local: aabb.max - aabb.min,
+            offset_curve: aabb.min,
+            extent_curve: aabb.max - aabb.min,
+        }
+        .extrude(4.0),
+        &brush,
+        grr_2d::FillRule::NonZero,
+    );
+}
+
+fn fill_curve(name: &str, curves: &[grr_2d::Curve]) {
+    let mut gpu_data = grr_2d::GpuData::new();
+    fill(
+        &mut gpu_data,
+        curves,
+        grr_2d::Brush::Color([40, 60, 160, 255]),
+    );
+    check(name, &gpu_data, 96, 96);
+}
+
+#[test]
+fn glyphs() {
+    const ROBOTO: &[u8] = include_bytes!("../assets/Roboto-Regular.ttf");
+
+    let mut gpu_data = grr_2d::GpuData::new();
+    let font = grr_2d::Font::from_bytes(&ROBOTO).unwrap();
+    let glyphs = grr_2d::Layout::default().calculate_glyphs(
+        &[font],
+        &grr_2d::SectionGeometry {
+            screen_position: (0.0, 0.0),
+            ..grr_2d::SectionGeometry::default()
+        },
+        &[grr_2d::SectionText {
+            text: "Quick fox @&%",
+            scale: grr_2d::Scale::uniform(48.0),
+            ..grr_2d::SectionText::default()
+        }],
+    );
+
+    for (glyph, _, _) in glyphs {
+        use rusttype::Segment;
+
+        let bbox = match glyph.unpositioned().exact_bounding_box() {
+            Some(bbox) => bbox,
+            None => continue, // whitespace
+        };
+        let shapes = glyph.unpositioned().shape().unwrap();
+        let mut pos = glyph.position();
+        pos.y -= bbox.max.y + bbox.min.y;
+
+        let mut path = grr_2d::PathBuilder::new();
+        for shape in shapes {
+            for segment in &shape.segments {
+                match segment {
+                    Segment::Line(line) => {
+                        path = path.move_to(glm::vec2(line.p[0].x, line.p[0].y));
+                        path = path.line_to(glm::vec2(line.p[1].x, line.p[1].y));
+                    }
+                    Segment::Curve(curve) => {
+                        path = path.move_to(glm::vec2(curve.p[0].x, curve.p[0].y));
+                        path = path.quad_to(
+                            glm::vec2(curve.p[1].x, curve.p[1].y),
+                            glm::vec2(curve.p[2].x, curve.p[2].y),
+                        );
+                    }
+                }
+            }
+        }
+
+        let to_curve = grr_2d::Transform::translate(glm::vec2(-bbox.min.x, bbox.max.y));
+        let curves = path.transform(&to_curve).monotonize().fill().finish();
+        let extent = glm::vec2(
+            bbox.max.x as f32 - bbox.min.x as f32,
+            bbox.max.y as f32 - bbox.min.y as f32,
+        );
+
+        gpu_data.extend(
+            &curves,
+            grr_2d::Rect {
+                offset_local: glm::vec2(pos.x + bbox.min.x as f32, pos.y + bbox.min.y as f32),
+                extent_local: extent,
+                offset_curve: glm::vec2(0.0, 0.0),
+                extent_curve: extent,
+            },
+            &grr_2d::Brush::Color([0, 0, 0, 255]),
+            grr_2d::FillRule::NonZero,
+        );
+    }
+
+    check("glyphs", &gpu_data, 384, 64);
+}
+
+#[test]
+fn lanka_stroke() {
+    let path = grr_2d::PathBuilder::from_svg_path("M 0 0 Q 100 200 140 100")
+        .unwrap()
+        .monotonize()
+        .stroke(&grr_2d::StrokeStyle {
+            join: grr_2d::CurveJoin::Round,
+            ..grr_2d::StrokeStyle::new(40.0)
+        });
+
+    let mut gpu_data = grr_2d::GpuData::new();
+    fill(&mut gpu_data, &path, grr_2d::Brush::Color([255, 0, 0, 255]));
+    check("lanka_stroke", &gpu_data, 160, 160);
+}
+
+#[test]
+fn lanka_rect() {
+    let mut gpu_data = grr_2d::GpuData::new();
+    fill(
+        &mut gpu_data,
+        &[grr_2d::Curve::Rect {
+            p0: glm::vec2(30.0, 20.0),
+            p1: glm::vec2(200.0, 50.0),
+        }],
+        grr_2d::Brush::Color([100, 100, 200, 255]),
+    );
+    check("lanka_rect", &gpu_data, 192, 48);
+}
+
+#[test]
+fn curve_line() {
+    let curves = grr_2d::PathBuilder::new()
+        .move_to(glm::vec2(0.0, 0.0))
+        .line_to(glm::vec2(80.0, 10.0))
+        .line_to(glm::vec2(30.0, 70.0))
+        .close()
+        .fill()
+        .finish();
+    fill_curve("curve_line", &curves);
+}
+
+#[test]
+fn curve_quad() {
+    let curves = grr_2d::PathBuilder::new()
+        .move_to(glm::vec2(0.0, 0.0))
+        .quad_to(glm::vec2(40.0, 90.0), glm::vec2(80.0, 0.0))
+        .close()
+        .monotonize()
+        .fill()
+        .finish();
+    fill_curve("curve_quad", &curves);
+}
+
+#[test]
+fn curve_cubic() {
+    let curves = grr_2d::PathBuilder::new()
+        .move_to(glm::vec2(0.0, 0.0))
+        .cubic_to(
+            glm::vec2(0.0, 80.0),
+            glm::vec2(80.0, -20.0),
+            glm::vec2(80.0, 60.0),
+        )
+        .close()
+        .fill()
+        .finish();
+    fill_curve("curve_cubic", &curves);
+}
+
+#[test]
+fn curve_circle() {
+    fill_curve(
+        "curve_circle",
+        &[grr_2d::Curve::Circle {
+            center: glm::vec2(0.0, 0.0),
+            radius: 40.0,
+        }],
+    );
+}
+
+#[test]
+fn curve_arc() {
+    let center = glm::vec2(0.0, 0.0);
+    let curves = grr_2d::PathBuilder::new()
+        .move_to(center)
+        .line_to(glm::vec2(40.0, 0.0))
+        .arc_to(center, glm::vec2(0.0, 40.0))
+        .close()
+        .monotonize()
+        .fill()
+        .finish();
+    fill_curve("curve_arc", &curves);
+}
+
+#[test]
+fn curve_ellipse() {
+    fill_curve(
+        "curve_ellipse",
+        &[grr_2d::Curve::Ellipse {
+            center: glm::vec2(0.0, 0.0),
+            radii: glm::vec2(40.0, 20.0),
+            rotation: 0.5,
+        }],
+    );
+}
+
+#[test]
+fn curve_rect() {
+    fill_curve(
+        "curve_rect",
+        &[grr_2d::Curve::Rect {
+            p0: glm::vec2(0.0, 0.0),
+            p1: glm::vec2(80.0, 50.0),
+        }],
+    );
+}
+
+#[test]
+fn curve_rounded_rect() {
+    fill_curve(
+        "curve_rounded_rect",
+        &[grr_2d::Curve::RoundedRect {
+            p0: glm::vec2(0.0, 0.0),
+            p1: glm::vec2(80.0, 50.0),
+            radii: [4.0, 12.0, 20.0, 0.0],
+        }],
+    );
+}
+
+#[test]
+fn curve_shadow_rect() {
+    fill_curve(
+        "curve_shadow_rect",
+        &[grr_2d::Curve::ShadowRect {
+            p0: glm::vec2(0.0, 0.0),
+            p1: glm::vec2(60.0, 40.0),
+            radius: 8.0,
+            sigma: 6.0,
+            spread: 2.0,
+        }],
+    );
+}
+
+#[test]
+fn blend_darken() {
+    let mut gpu_data = grr_2d::GpuData::new();
+
+    // opaque backdrop, light and dark half
+    fill(
+        &mut gpu_data,
+        &[grr_2d::Curve::Rect {
+            p0: glm::vec2(0.0, 0.0),
+            p1: glm::vec2(60.0, 80.0),
+        }],
+        grr_2d::Brush::Color([250, 230, 120, 255]),
+    );
+    fill(
+        &mut gpu_data,
+        &[grr_2d::Curve::Rect {
+            p0: glm::vec2(60.0, 0.0),
+            p1: glm::vec2(120.0, 80.0),
+        }],
+        grr_2d::Brush::Color([30, 40, 110, 255]),
+    );
+
+    gpu_data.set_blend_mode(grr_2d::BlendMode::Darken);
+    fill(
+        &mut gpu_data,
+        &[grr_2d::Curve::Circle {
+            center: glm::vec2(40.0, 40.0),
+            radius: 30.0,
+        }],
+        grr_2d::Brush::Color([200, 60, 60, 255]),
+    );
+    gpu_data.set_blend_mode(grr_2d::BlendMode::SrcOver);
+
+    // the layer is darkened as a whole
+    gpu_data.push_layer(0.8, grr_2d::BlendMode::Darken);
+    fill(
+        &mut gpu_data,
+        &[grr_2d::Curve::Circle {
+            center: glm::vec2(80.0, 40.0),
+            radius: 30.0,
+        }],
+        grr_2d::Brush::Color([60, 200, 60, 255]),
+    );
+    gpu_data.pop_layer();
+
+    check("blend_darken", &gpu_data, 120, 80);
+}
+
+fn stops(colors: &[grr_2d::Color]) -> Vec<grr_2d::GradientStop> {
+    colors
+        .iter()
+        .enumerate()
+        .map(|(i, &color)| grr_2d::GradientStop {
+            offset: i as f32 / (colors.len() - 1) as f32,
+            color,
+        })
+        .collect()
+}
+
+#[test]
+fn gradients() {
+    let mut gpu_data = grr_2d::GpuData::new();
+    let colors = [[220, 40, 40, 255], [250, 220, 60, 255], [40, 80, 200, 255]];
+
+    fill(
+        &mut gpu_data,
+        &[grr_2d::Curve::Rect {
+            p0: glm::vec2(0.0, 0.0),
+            p1: glm::vec2(60.0, 60.0),
+        }],
+        grr_2d::Brush::LinearGradient {
+            start: glm::vec2(10.0, 10.0),
+            end: glm::vec2(50.0, 50.0),
+            stops: stops(&colors),
+            spread: grr_2d::SpreadMode::Pad,
+        },
+    );
+    fill(
+        &mut gpu_data,
+        &[grr_2d::Curve::Circle {
+            center: glm::vec2(100.0, 30.0),
+            radius: 30.0,
+        }],
+        grr_2d::Brush::RadialGradient {
+            center: glm::vec2(100.0, 30.0),
+            radius: 15.0,
+            focal: glm::vec2(90.0, 35.0),
+            stops: stops(&colors),
+            spread: grr_2d::SpreadMode::Reflect,
+        },
+    );
+    fill(
+        &mut gpu_data,
+        &[grr_2d::Curve::Rect {
+            p0: glm::vec2(140.0, 0.0),
+            p1: glm::vec2(200.0, 60.0),
+        }],
+        grr_2d::Brush::SweepGradient {
+            center: glm::vec2(170.0, 30.0),
+            start_angle: 0.0,
+            end_angle: std::f32::consts::PI * 1.5,
+            stops: stops(&colors),
+        },
+    );
+
+    check("gradients", &gpu_data, 200, 60);
+}
+
+#[test]
+fn clip_nested() {
+    let mut gpu_data = grr_2d::GpuData::new();
+
+    gpu_data.push_clip(
+        &[grr_2d::Curve::Circle {
+            center: glm::vec2(40.0, 40.0),
+            radius: 36.0,
+        }],
+        grr_2d::FillRule::NonZero,
+    );
+    fill(
+        &mut gpu_data,
+        &[grr_2d::Curve::Rect {
+            p0: glm::vec2(0.0, 0.0),
+            p1: glm::vec2(80.0, 80.0),
+        }],
+        grr_2d::Brush::Color([40, 60, 160, 255]),
+    );
+
+    // intersection of both clips
+    gpu_data.push_clip(
+        &[grr_2d::Curve::Rect {
+            p0: glm::vec2(30.0, 10.0),
+            p1: glm::vec2(90.0, 50.0),
+        }],
+        grr_2d::FillRule::NonZero,
+    );
+    fill(
+        &mut gpu_data,
+        &[grr_2d::Curve::Rect {
+            p0: glm::vec2(0.0, 0.0),
+            p1: glm::vec2(80.0, 80.0),
+        }],
+        grr_2d::Brush::Color([240, 140, 40, 255]),
+    );
+    gpu_data.pop_clip();
+    gpu_data.pop_clip();
+
+    // unclipped again
+    fill(
+        &mut gpu_data,
+        &[grr_2d::Curve::Rect {
+            p0: glm::vec2(70.0, 60.0),
+            p1: glm::vec2(96.0, 80.0),
+        }],
+        grr_2d::Brush::Color([60, 160, 60, 255]),
+    );
+
+    check("clip_nested", &gpu_data, 96, 80);
+}
+
+#[test]
+fn layer_opacity() {
+    let mut gpu_data = grr_2d::GpuData::new();
+
+    fill(
+        &mut gpu_data,
+        &[grr_2d::Curve::Rect {
+            p0: glm::vec2(0.0, 0.0),
+            p1: glm::vec2(120.0, 30.0),
+        }],
+        grr_2d::Brush::Color([40, 60, 160, 255]),
+    );
+
+    // overlapping circles don't add up inside the layer
+    gpu_data.push_layer(0.5, grr_2d::BlendMode::SrcOver);
+    fill(
+        &mut gpu_data,
+        &[grr_2d::Curve::Circle {
+            center: glm::vec2(40.0, 40.0),
+            radius: 30.0,
+        }],
+        grr_2d::Brush::Color([220, 40, 40, 255]),
+    );
+    fill(
+        &mut gpu_data,
+        &[grr_2d::Curve::Circle {
+            center: glm::vec2(80.0, 40.0),
+            radius: 30.0,
+        }],
+        grr_2d::Brush::Color([220, 40, 40, 255]),
+    );
+    gpu_data.pop_layer();
+
+    check("layer_opacity", &gpu_data, 120, 80);
+}